@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::youtube_url::{parse_youtube_url, YoutubeUrl};
+
+// Enlaces de YouTube de un export de marcadores (formato Netscape, el que usan todos los navegadores)
+pub fn extract_youtube_urls(html: &str) -> Vec<YoutubeUrl> {
+    let anchor = Regex::new(r#"(?i)<a\s[^>]*?href\s*=\s*"([^"]*)""#).unwrap();
+
+    let mut urls: Vec<YoutubeUrl> = Vec::new();
+    for caps in anchor.captures_iter(html) {
+        let href = caps[1].replace("&amp;", "&");
+        // Los marcadores que no son de YouTube se ignoran
+        let Ok(parsed) = parse_youtube_url(&href) else {
+            continue;
+        };
+        if !urls.contains(&parsed) {
+            urls.push(parsed);
+        }
+    }
+    urls
+}
+
+pub fn load(path: &Path) -> Result<Vec<YoutubeUrl>, String> {
+    let html = std::fs::read_to_string(path)
+        .map_err(|e| format!("No se pudo leer el archivo de marcadores {:?}: {}", path, e))?;
+
+    Ok(extract_youtube_urls(&html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_only_youtube_links() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><H3>Música</H3>
+    <DL><p>
+        <DT><A HREF="https://www.youtube.com/watch?v=dQw4w9WgXcQ&amp;list=PL1234abcd" ADD_DATE="1700000000">Tema</A>
+        <DT><A HREF="https://example.com/" ADD_DATE="1700000000">Otro sitio</A>
+        <DT><A HREF="https://youtu.be/dQw4w9WgXcQ?si=abc">Repetido</A>
+        <DT><A ADD_DATE="1" HREF="https://music.youtube.com/playlist?list=OLAK5uy_abc">Álbum</A>
+    </DL><p>
+</DL><p>"#;
+
+        let urls = extract_youtube_urls(html);
+        assert_eq!(urls.len(), 3);
+        assert!(urls[0].is_ambiguous());
+        assert_eq!(urls[1].video_id.as_deref(), Some("dQw4w9WgXcQ"));
+        assert!(urls[2].is_album());
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use clap::{ArgAction, Parser};
+
+// Códigos de salida de los modos sin interfaz
+pub const EXIT_PARTIAL_FAILURE: i32 = 1;
+// El mismo que usa clap para los errores de uso
+pub const EXIT_BAD_ARGUMENTS: i32 = 2;
+pub const EXIT_TOTAL_FAILURE: i32 = 3;
+pub const EXIT_STARTUP_ERROR: i32 = 4;
+
+const EXIT_CODES_HELP: &str = "Códigos de salida (modos sin interfaz):
+  0  todas las descargas pedidas terminaron bien
+  1  algunas descargas fallaron
+  2  argumentos inválidos (opciones, enlaces o archivos de entrada)
+  3  fallaron todas las descargas
+  4  error al iniciar (config.toml, binarios de yt-dlp/ffmpeg)";
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Descarga el audio de videos de YouTube a una carpeta o pendrive",
+    after_help = EXIT_CODES_HELP
+)]
+pub struct Cli {
+    /// Muestra título, autor y duración del video y sale sin descargar nada
+    #[arg(long, value_name = "URL")]
+    pub print_only: Option<String>,
+
+    /// Enlaces a descargar sin abrir la interfaz (modo headless)
+    #[arg(value_name = "URL")]
+    pub urls: Vec<String>,
+
+    /// Carpeta de destino; si no se indica se pregunta al iniciar
+    #[arg(long, value_name = "RUTA")]
+    pub dest: Option<String>,
+
+    /// Carpeta adicional donde se copia cada descarga; se puede repetir (ej. pendrive y NAS)
+    #[arg(long, value_name = "RUTA")]
+    pub also_dest: Vec<String>,
+
+    /// En modo headless, si el enlace tiene `list=` descarga la playlist completa
+    #[arg(long)]
+    pub playlist: bool,
+
+    /// Agrega a la cola los enlaces de YouTube de un export de marcadores HTML del navegador
+    #[arg(long, value_name = "ARCHIVO")]
+    pub import_bookmarks: Option<PathBuf>,
+
+    /// Encola el enlace copiado en el portapapeles al iniciar; si no es válido se abre la interfaz normal
+    #[arg(long)]
+    pub from_clipboard: bool,
+
+    /// Junto con --from-clipboard, descarga sin abrir la interfaz y sale
+    #[arg(long, requires = "from_clipboard")]
+    pub headless: bool,
+
+    /// Lee enlaces de la entrada estándar (uno por línea) y los descarga sin interfaz (en --dest o dest_dir de config.toml)
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Sin interfaz y hasta Ctrl+C: descarga los enlaces de los .url/.txt que aparezcan en la carpeta
+    /// (o en watch_dir de config.toml) y borra cada archivo ya leído
+    #[arg(long, value_name = "CARPETA", num_args = 0..=1, default_missing_value = "", conflicts_with = "stdin")]
+    pub watch: Option<PathBuf>,
+
+    /// Argumentos extra para yt-dlp, con comillas como en la terminal (avanzado, sin soporte)
+    #[arg(long, value_name = "ARGS", allow_hyphen_values = true)]
+    pub yt_dlp_args: Option<String>,
+
+    /// Descarga exactamente este formato de yt-dlp (el id que muestra `yt-dlp -F`, ej. 251)
+    #[arg(long, value_name = "ID")]
+    pub format_id: Option<String>,
+
+    /// Junto con --format-id, deja el códec original sin convertirlo a audio_format
+    #[arg(long, requires = "format_id")]
+    pub no_reencode: bool,
+
+    /// Trata los enlaces como charlas o podcasts: se guardan en speech_audio_format de config.toml
+    #[arg(long, conflicts_with = "music")]
+    pub speech: bool,
+
+    /// Trata los enlaces como música aunque detect_speech esté activado
+    #[arg(long)]
+    pub music: bool,
+
+    /// Pista de audio por idioma en videos doblados (ej. ja); si no existe se usa la original
+    #[arg(long, value_name = "IDIOMA")]
+    pub audio_lang: Option<String>,
+
+    /// Vacía la caché de metadata (también metadata_cache.json) y la vuelve a pedir a YouTube
+    #[arg(long)]
+    pub refresh_metadata: bool,
+
+    /// Género que se incrusta en las descargas de esta sesión (pisa genre y genre_folders de config.toml)
+    #[arg(long, value_name = "GÉNERO")]
+    pub genre: Option<String>,
+
+    /// Además del archivo completo, guarda cada capítulo del video como un tema aparte
+    #[arg(long)]
+    pub split_chapters: bool,
+
+    /// Muestra cuánto tardó cada fase de cada descarga (metadata, descarga, proceso, movimiento)
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Descarga aunque el audio no llegue a min_audio_bitrate de config.toml
+    #[arg(long)]
+    pub any_bitrate: bool,
+
+    /// No borra al iniciar los parciales (.part, .ytdl) ni los archivos vacíos de ./output (para depurar)
+    #[arg(long)]
+    pub no_repair: bool,
+
+    /// Omite la verificación inicial de binarios, conexión y carpeta de destino
+    #[arg(long)]
+    pub no_check: bool,
+
+    /// Sin interfaz, solo muestra los errores (en stderr); el resultado queda en el código de salida
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Más detalle en el log (-v debug, -vv trace); con la interfaz se escribe en gif_tube_desk.log
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Lista los discos extraíbles (nombre, montaje, espacio libre) y sale
+    #[arg(long)]
+    pub list_disks: bool,
+
+    /// Muestra cuánto ocupan ./libs, ./output y la caché de yt-dlp
+    #[arg(long)]
+    pub storage: bool,
+
+    /// Junto con --storage, borra los temporales de ./output, la caché y lo que sobre en ./libs (con la app cerrada)
+    #[arg(long, requires = "storage")]
+    pub prune: bool,
+
+    /// Junto con --prune, borra también yt-dlp y ffmpeg (se vuelven a descargar al iniciar)
+    #[arg(long, requires = "prune")]
+    pub force: bool,
+
+    /// Con --stdin/--watch encola aunque el mismo video ya esté en la cola
+    #[arg(long)]
+    pub allow_duplicates: bool,
+
+    /// Descarga de cualquier canal, aunque no esté en channel_allowlist de config.toml
+    #[arg(long)]
+    pub any_channel: bool,
+
+    /// Borra la última descarga del historial (y su carpeta si queda vacía), con confirmación
+    #[arg(long)]
+    pub undo: bool,
+
+    /// Guarda en un JSON los temas de la carpeta de --dest (o dest_dir) según el historial, para respaldo o migración
+    #[arg(long, value_name = "ARCHIVO", conflicts_with = "import_manifest")]
+    pub export_manifest: Option<PathBuf>,
+
+    /// Agrega al historial los temas que ya estaban en --dest (o dest_dir), sin enlace, para que cuenten al buscar duplicados
+    #[arg(long)]
+    pub scan_library: bool,
+
+    /// Reconstruye el historial desde un manifiesto exportado; con --dest los archivos se buscan en esa carpeta
+    #[arg(long, value_name = "ARCHIVO")]
+    pub import_manifest: Option<PathBuf>,
+
+    /// Revisa los archivos del historial y muestra los que no siguen el esquema de nombres actual
+    #[arg(long)]
+    pub repair_library: bool,
+
+    /// Vuelve a escribir las etiquetas de los archivos del historial con la metadata de su enlace
+    #[arg(long)]
+    pub retag: bool,
+
+    /// Junto con --retag, solo muestra los cambios sin tocar los archivos
+    #[arg(long, requires = "retag")]
+    pub dry_run: bool,
+
+    /// Junto con --repair-library, renombra los archivos en vez de solo mostrarlos
+    #[arg(long, requires = "repair_library")]
+    pub apply: bool,
+}
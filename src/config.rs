@@ -0,0 +1,696 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::title_noise;
+use crate::title_tags::{self, TagRule};
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+pub const DEFAULT_METADATA_BASE_URL: &str = "https://www.youtube.com";
+
+// Formatos sin códecs propietarios ni patentes
+pub const FREE_AUDIO_FORMATS: [&str; 4] = ["opus", "vorbis", "flac", "wav"];
+
+// Contenedores de audio que acepta --remux-video de yt-dlp
+const REMUX_CONTAINERS: [&str; 10] = ["aac", "aiff", "alac", "flac", "m4a", "mka", "mp3", "ogg", "opus", "wav"];
+
+// Más rápido no se nota; más lento las teclas tardan en responder
+const MIN_UI_REFRESH_MS: u64 = 20;
+const MAX_UI_REFRESH_MS: u64 = 2000;
+
+// Frecuencias que aceptan los códecs de audio soportados
+const SAMPLE_RATES: [u32; 9] = [8000, 11025, 16000, 22050, 24000, 32000, 44100, 48000, 96000];
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct GenreFolder {
+    pub folder: String,
+    pub genre: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    // Carpeta de destino por defecto; --dest la reemplaza
+    pub dest_dir: Option<String>,
+    // Carpetas donde además se copia cada audio (ej. un pendrive y el NAS); --also-dest agrega más
+    pub extra_dest_dirs: Vec<String>,
+    pub audio_format: String,
+    pub audio_quality: String,
+    // Formato exacto de yt-dlp (el id que muestra `yt-dlp -F`, ej. "251"); reemplaza la elección automática
+    pub audio_format_id: Option<String>,
+    // Pista de audio por idioma en videos doblados (ej. "ja"); si no existe se usa la original
+    pub audio_language: Option<String>,
+    // Con audio_format_id, convertir igual a audio_format; si no, queda el códec original sin recodificar
+    pub reencode_format_id: bool,
+    // Cambia solo el contenedor, sin recodificar (ej. "ogg", "mka" o reglas de yt-dlp como "webm>ogg/mka");
+    // reemplaza a audio_format y la extensión final es la del contenedor
+    pub remux_container: Option<String>,
+    // Prefiere formatos libres: baja el opus/vorbis de YouTube y, si audio_format es mp3/aac/m4a/alac, extrae a opus
+    pub prefer_free_formats: bool,
+    // Bitrate mínimo (kbps) del mejor audio disponible; por debajo se omite el tema (sin valor = sin mínimo)
+    pub min_audio_bitrate: Option<u32>,
+    // Formato para charlas, podcasts y audiolibros (los elementos marcados como voz, o detectados con detect_speech)
+    pub speech_audio_format: String,
+    // Sin marca en la cola, trata como voz lo que no es música y dura al menos speech_min_minutes
+    pub detect_speech: bool,
+    pub speech_min_minutes: u64,
+    // Formatos adicionales convertidos desde la misma descarga (ej. ["flac"])
+    pub extra_audio_formats: Vec<String>,
+    // Además del audio, descargar el video (mp4) y guardarlo en `videos/`
+    pub keep_video: bool,
+    // Para videos bloqueados por región (desactivado por defecto)
+    pub geo_bypass: bool,
+    pub geo_bypass_country: Option<String>,
+    // Acepta enlaces de otros sitios que soporta yt-dlp (SoundCloud, Bandcamp, ...)
+    pub allow_other_sites: bool,
+    // User-agent para yt-dlp y para las consultas de metadata
+    pub user_agent: Option<String>,
+    // Servidor que responde `/oembed?url=...` como YouTube (ej. una instancia propia para no consultar a Google)
+    pub metadata_base_url: String,
+    // Si oembed falla (TLS, proxy, red bloqueada) se pide la metadata a yt-dlp, que maneja su propia conexión.
+    // Es más lento y hace una consulta extra a YouTube por tema; solo para redes donde oembed no anda
+    pub oembed_fallback_yt_dlp: bool,
+    // Guarda la caché de metadata en metadata_cache.json para no volver a pedirla en otra sesión (--refresh-metadata la vacía)
+    pub cache_metadata_on_disk: bool,
+    // Quita los segmentos sin música (intros, charlas) marcados en SponsorBlock
+    pub sponsorblock: bool,
+    // Cada capítulo del video se guarda además como un tema (carpeta con el título del video, "NN - capítulo")
+    pub split_chapters: bool,
+    // Normaliza el volumen con el filtro loudnorm de ffmpeg
+    pub normalize_audio: bool,
+    // Portada incrustada en el audio, convertida a cuadrada
+    pub embed_thumbnail: bool,
+    pub thumbnail_format: ThumbnailFormat,
+    pub thumbnail_fit: ThumbnailFit,
+    // Forma de los nombres: "legacy", "artist-title", "title-only" o "artist/title"
+    pub naming_scheme: NamingScheme,
+    // Con "legacy", si se antepone el artista: "always", "never" o "auto" (no, si el título ya empieza con el artista)
+    pub artist_prefix: ArtistPrefix,
+    // Conserva el nombre que le pone yt-dlp (solo se limpian los caracteres inválidos); la carpeta sigue el esquema
+    pub keep_original_name: bool,
+    // Se niega a escribir fuera de la carpeta destino (un ".." o una ruta absoluta colados en un título)
+    pub safe_paths: bool,
+    // Subcarpetas por inicial del artista para bibliotecas grandes: "none" o "first-letter" (Música/A/Artista/...)
+    pub library_shards: ShardScheme,
+    // Agrega la duración al nombre del archivo, ej. "Artista-Set [01.02.03]" (útil para sets de DJ)
+    pub duration_in_filename: bool,
+    // Quita del nombre de archivo grupos como "(Official Video)" o "[HD]"
+    pub strip_title_noise: bool,
+    // Regex contra el texto entre () o []; reemplaza la lista por defecto
+    pub title_noise_patterns: Vec<String>,
+    // Unifica "feat."/"ft."/"featuring" y las etiquetas de remix/edit antes de armar el nombre
+    pub normalize_title_tags: bool,
+    // Reglas (regex y reemplazo) que se aplican en orden; reemplazan la lista por defecto
+    pub title_tag_rules: Vec<TagRule>,
+    // Al incrustar la metadata, el enlace original va al comentario (y opcionalmente la fecha de subida)
+    pub url_comment: bool,
+    pub url_comment_upload_date: bool,
+    // Género que se incrusta junto con el resto de la metadata (YouTube casi nunca lo trae); gana sobre genre_folders
+    pub genre: Option<String>,
+    // Género según la subcarpeta del destino, ej. { folder = "Rock", genre = "Rock" } para D:/Musica/Rock
+    pub genre_folders: Vec<GenreFolder>,
+    // Archivo con la metadata completa junto al audio: "json" (.info.json) o "nfo" (Kodi)
+    pub sidecar_format: Option<SidecarFormat>,
+    // Subtítulos guardados como .srt junto al audio, ej. ["es", "en"]; vacío = no se descargan
+    pub subtitle_langs: Vec<String>,
+    // Si no hay subtítulos hechos a mano en esos idiomas, usar los generados automáticamente
+    pub auto_subtitles: bool,
+    // Guarda además la miniatura como imagen junto al audio (desactivado por defecto)
+    pub save_thumbnail: bool,
+    // Ancho máximo de esa imagen; sin valor se deja la de mayor resolución
+    pub thumbnail_max_width: Option<u32>,
+    // Antigüedad máxima de los restos en `output` antes de borrarlos (0 = nunca)
+    pub staging_max_age_hours: u64,
+    // Carpeta local rápida para los parciales de yt-dlp (si `output` está en un disco de red); el resultado va igual a `output`
+    pub download_temp_dir: Option<String>,
+    // Carpeta que vigila --watch (ej. donde una extensión del navegador deja archivos .url). Cada cuánto se vuelven a
+    // mirar los archivos a medio escribir, o toda la carpeta si el sistema no avisa cambios (ej. algunas unidades de red)
+    pub watch_dir: Option<String>,
+    pub watch_interval_secs: u64,
+    // Horas entre actualizaciones de yt-dlp al iniciar (0 = en cada inicio)
+    pub binaries_update_hours: u64,
+    // Frecuencia de muestreo (Hz) y canales del audio final; sin valor se respetan los del original
+    pub audio_sample_rate: Option<u32>,
+    pub audio_channels: Option<u8>,
+    // Descargas simultáneas; cada una trabaja en su propia carpeta dentro de `output`
+    pub max_concurrent_downloads: usize,
+    // Descargas simultáneas contra un mismo sitio (todo YouTube cuenta como uno), para no provocar límites de uso
+    pub max_concurrent_per_host: usize,
+    // Consultas de metadata simultáneas para mostrar los títulos de la cola antes de descargar
+    pub max_concurrent_metadata: usize,
+    // Qué hacer si falla un tema de una playlist: "continue" o "fail-fast"
+    pub playlist_error_policy: PlaylistErrorPolicy,
+    // Entradas de una playlist que se leen de una vez; la siguiente página se pide al terminar estas
+    pub playlist_page_size: usize,
+    // Tope por sesión para conexiones medidas: al llegarse no se empiezan más descargas (Ctrl+N reinicia la cuenta)
+    pub session_max_downloads: Option<u32>,
+    pub session_max_mib: Option<u64>,
+    // Reintentos de una descarga fallida (no se reintenta un video privado o eliminado)
+    pub download_retries: u32,
+    // Reintentos de red de yt-dlp (--retries / --fragment-retries): un número o "infinite"
+    pub network_retries: Retries,
+    pub fragment_retries: Retries,
+    // Reintentos de mover al destino un archivo ya descargado (sin volver a descargarlo)
+    pub move_retries: u32,
+    // Si un nombre ya tiene más copias que esto ("_N"), la interfaz pregunta si sobrescribir, omitir o renombrar
+    pub collision_confirm_after: Option<u32>,
+    // Canales o artistas (nombre o id) que se descargan sin preguntar; vacía, no se controla nada.
+    // Un canal que no está se confirma en la interfaz; sin interfaz se omite (salvo --force)
+    pub channel_allowlist: Vec<String>,
+    // Compara la huella de audio (fpcalc) con el historial antes de mover el archivo
+    pub detect_duplicates: bool,
+    // Qué hacer con un duplicado probable: "keep" (avisa y lo guarda igual) o "skip" (lo descarta)
+    pub duplicate_policy: DuplicatePolicy,
+    // Argumentos extra para el ffmpeg de la extracción de audio (ej. "-threads 2")
+    pub postprocessor_args: Option<String>,
+    // Opción avanzada sin soporte: argumentos crudos que se agregan al final del comando de yt-dlp
+    pub extra_yt_dlp_args: Option<String>,
+    // error, warn, info, debug o trace
+    pub log_level: String,
+    // Muestra cuánto tardó cada fase (metadata, descarga, proceso, movimiento); --timings lo activa
+    pub timings: bool,
+    // Cada cuánto se redibuja la interfaz (ms); más alto consume menos CPU en equipos chicos
+    pub ui_refresh_ms: u64,
+    // Avance de descargas y lotes: "text" (solo el porcentaje) o "blocks" (barra ████░░)
+    pub progress_style: ProgressStyle,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    Jpg,
+    Png,
+}
+
+// Cómo se lleva la miniatura 16:9 a formato cuadrado
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFit {
+    Crop,
+    Pad,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarFormat {
+    Json,
+    Nfo,
+}
+
+// Los temas de un álbum siempre van en "Artista/Álbum/NN - Título", sea cual sea el esquema
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamingScheme {
+    // Como antes: "Artista/Artista-Título", o "Artista/Título" si el título ya nombra al artista
+    Legacy,
+    // "Artista-Título" directamente en el destino
+    ArtistTitle,
+    // "Título" directamente en el destino
+    TitleOnly,
+    // "Artista/Título"
+    #[serde(rename = "artist/title")]
+    ArtistFolder,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtistPrefix {
+    Always,
+    Never,
+    Auto,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShardScheme {
+    None,
+    FirstLetter,
+}
+
+// Todo lo que decide dónde queda un archivo dentro del destino
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibraryLayout {
+    pub naming: NamingScheme,
+    pub shards: ShardScheme,
+    pub keep_original_name: bool,
+    pub artist_prefix: ArtistPrefix,
+    pub safe_paths: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaylistErrorPolicy {
+    Continue,
+    FailFast,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStyle {
+    Text,
+    Blocks,
+}
+
+// Qué es un elemento de la cola; decide el formato por defecto
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Music,
+    Speech,
+}
+
+impl ContentKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ContentKind::Music => "música",
+            ContentKind::Speech => "voz",
+        }
+    }
+}
+
+// En config.toml: `network_retries = 5` o `network_retries = "infinite"`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "RawRetries", into = "RawRetries")]
+pub enum Retries {
+    Times(u32),
+    Infinite,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RawRetries {
+    Times(u32),
+    Word(String),
+}
+
+impl TryFrom<RawRetries> for Retries {
+    type Error = String;
+
+    fn try_from(raw: RawRetries) -> Result<Self, Self::Error> {
+        match raw {
+            RawRetries::Times(times) => Ok(Retries::Times(times)),
+            RawRetries::Word(word) if word == "infinite" => Ok(Retries::Infinite),
+            RawRetries::Word(word) => Err(format!("los reintentos deben ser un número o \"infinite\": {:?}", word)),
+        }
+    }
+}
+
+impl From<Retries> for RawRetries {
+    fn from(retries: Retries) -> Self {
+        match retries {
+            Retries::Times(times) => RawRetries::Times(times),
+            Retries::Infinite => RawRetries::Word("infinite".to_string()),
+        }
+    }
+}
+
+// Como lo recibe yt-dlp
+impl fmt::Display for Retries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Retries::Times(times) => write!(f, "{}", times),
+            Retries::Infinite => write!(f, "infinite"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    Keep,
+    Skip,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            dest_dir: None,
+            extra_dest_dirs: Vec::new(),
+            audio_format: "mp3".to_string(),
+            audio_quality: "0".to_string(),
+            audio_format_id: None,
+            audio_language: None,
+            reencode_format_id: true,
+            remux_container: None,
+            prefer_free_formats: false,
+            min_audio_bitrate: None,
+            speech_audio_format: "opus".to_string(),
+            detect_speech: false,
+            speech_min_minutes: 20,
+            extra_audio_formats: Vec::new(),
+            keep_video: false,
+            geo_bypass: false,
+            geo_bypass_country: None,
+            allow_other_sites: false,
+            user_agent: None,
+            metadata_base_url: DEFAULT_METADATA_BASE_URL.to_string(),
+            oembed_fallback_yt_dlp: false,
+            cache_metadata_on_disk: false,
+            sponsorblock: false,
+            split_chapters: false,
+            normalize_audio: false,
+            embed_thumbnail: false,
+            thumbnail_format: ThumbnailFormat::Jpg,
+            thumbnail_fit: ThumbnailFit::Crop,
+            naming_scheme: NamingScheme::Legacy,
+            artist_prefix: ArtistPrefix::Auto,
+            keep_original_name: false,
+            safe_paths: true,
+            library_shards: ShardScheme::None,
+            duration_in_filename: false,
+            strip_title_noise: true,
+            title_noise_patterns: title_noise::DEFAULT_NOISE_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            normalize_title_tags: false,
+            title_tag_rules: title_tags::default_rules(),
+            url_comment: true,
+            url_comment_upload_date: false,
+            genre: None,
+            genre_folders: Vec::new(),
+            sidecar_format: None,
+            subtitle_langs: Vec::new(),
+            auto_subtitles: false,
+            save_thumbnail: false,
+            thumbnail_max_width: None,
+            staging_max_age_hours: 24,
+            watch_dir: None,
+            watch_interval_secs: 2,
+            download_temp_dir: None,
+            binaries_update_hours: 24,
+            audio_sample_rate: None,
+            audio_channels: None,
+            max_concurrent_downloads: 1,
+            max_concurrent_per_host: 2,
+            max_concurrent_metadata: 2,
+            playlist_error_policy: PlaylistErrorPolicy::Continue,
+            playlist_page_size: 100,
+            session_max_downloads: None,
+            session_max_mib: None,
+            download_retries: 2,
+            network_retries: Retries::Times(10),
+            fragment_retries: Retries::Times(10),
+            move_retries: 2,
+            collision_confirm_after: None,
+            channel_allowlist: Vec::new(),
+            detect_duplicates: false,
+            duplicate_policy: DuplicatePolicy::Keep,
+            postprocessor_args: None,
+            extra_yt_dlp_args: None,
+            log_level: "info".to_string(),
+            timings: false,
+            ui_refresh_ms: 100,
+            progress_style: ProgressStyle::Text,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("No se pudo leer {:?}: {}", path, e))?;
+
+        let config: Config = toml::from_str(&raw)
+            .map_err(|e| format!("Configuración inválida en {:?}: {}", path, e))?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Una marca explícita gana sobre la detección automática
+    pub fn apply_kind(&mut self, kind: ContentKind) {
+        match kind {
+            ContentKind::Music => self.detect_speech = false,
+            ContentKind::Speech => self.audio_format = self.speech_audio_format.clone(),
+        }
+    }
+
+    // El formato que se extrae de verdad, contando prefer_free_formats
+    pub fn effective_audio_format(&self) -> &str {
+        if self.prefer_free_formats && !FREE_AUDIO_FORMATS.contains(&self.audio_format.as_str()) {
+            "opus"
+        } else {
+            &self.audio_format
+        }
+    }
+
+    // `-f` de yt-dlp: el id pedido, o los streams opus/vorbis primero si se prefieren formatos libres
+    pub fn audio_format_selector(&self) -> Option<String> {
+        if let Some(id) = &self.audio_format_id {
+            return Some(id.clone());
+        }
+
+        let codecs: &[&str] = if self.prefer_free_formats { &["[acodec=opus]", "[acodec=vorbis]"] } else { &[] };
+        let mut choices = Vec::new();
+        if let Some(language) = &self.audio_language {
+            choices.extend(codecs.iter().map(|codec| format!("bestaudio[language^={}]{}", language, codec)));
+            choices.push(format!("bestaudio[language^={}]", language));
+        }
+        choices.extend(codecs.iter().map(|codec| format!("bestaudio{}", codec)));
+
+        if choices.is_empty() {
+            return None;
+        }
+        choices.push("bestaudio".to_string());
+        choices.push("best".to_string());
+        Some(choices.join("/"))
+    }
+
+    // Sin recodificar yt-dlp deja el códec original y la extensión no se conoce de antemano
+    pub fn reencodes(&self) -> bool {
+        self.remux_container.is_none() && (self.audio_format_id.is_none() || self.reencode_format_id)
+    }
+
+    // Destino principal primero, sin repetir carpetas
+    pub fn dest_dirs(&self, primary: &str) -> Vec<PathBuf> {
+        let mut dirs = vec![PathBuf::from(primary)];
+        for extra in &self.extra_dest_dirs {
+            let extra = PathBuf::from(extra.trim());
+            if !extra.as_os_str().is_empty() && !dirs.contains(&extra) {
+                dirs.push(extra);
+            }
+        }
+        dirs
+    }
+
+    // `genre` gana; si no, la subcarpeta de genre_folders más larga que coincida con el final del destino
+    pub fn genre_for(&self, dest_dir: &Path) -> Option<&str> {
+        if let Some(genre) = &self.genre {
+            return Some(genre.as_str());
+        }
+
+        self.genre_folders
+            .iter()
+            .filter(|entry| dest_dir.ends_with(Path::new(entry.folder.trim())))
+            .max_by_key(|entry| Path::new(entry.folder.trim()).components().count())
+            .map(|entry| entry.genre.as_str())
+    }
+
+    // Reescribe el archivo completo (se pierden los comentarios que tuviera)
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let raw = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+
+        std::fs::write(path, raw).map_err(|e| format!("No se pudo guardar {:?}: {}", path, e))
+    }
+
+    // El límite de la sesión que ya se alcanzó, para mostrarlo ("500 MiB", "20 descargas")
+    pub fn session_limit_reached(&self, downloads: u32, bytes: u64) -> Option<String> {
+        if let Some(max) = self.session_max_mib.filter(|max| bytes >= max * 1024 * 1024) {
+            return Some(format!("{} MiB", max));
+        }
+        self.session_max_downloads
+            .filter(|max| downloads >= *max)
+            .map(|max| format!("{} descargas", max))
+    }
+
+    // Sin distinguir mayúsculas ni el " - Topic" de YouTube Music
+    pub fn allows_channel(&self, names: &[String]) -> bool {
+        let normalize = |name: &str| name.trim().trim_end_matches(" - Topic").to_lowercase();
+        self.channel_allowlist.is_empty()
+            || names
+                .iter()
+                .any(|name| self.channel_allowlist.iter().any(|allowed| normalize(allowed) == normalize(name)))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(args) = &self.postprocessor_args {
+            let args = args.trim();
+            if !args.starts_with('-') || args.chars().any(|c| c.is_control()) {
+                return Err(format!(
+                    "postprocessor_args debe ser una lista de opciones de ffmpeg (ej. \"-threads 2\"): {:?}",
+                    args
+                ));
+            }
+        }
+
+        if let Some(rate) = self.audio_sample_rate {
+            if !SAMPLE_RATES.contains(&rate) {
+                return Err(format!("audio_sample_rate inválido: {} (valores posibles: {:?})", rate, SAMPLE_RATES));
+            }
+        }
+
+        if let Some(channels) = self.audio_channels {
+            if !(1..=2).contains(&channels) {
+                return Err(format!("audio_channels debe ser 1 (mono) o 2 (estéreo): {}", channels));
+            }
+        }
+
+        title_noise::compile(&self.title_noise_patterns)?;
+        title_tags::compile(&self.title_tag_rules)?;
+
+        if self.genre.as_deref().is_some_and(|genre| genre.trim().is_empty())
+            || self.genre_folders.iter().any(|entry| entry.folder.trim().is_empty() || entry.genre.trim().is_empty())
+        {
+            return Err("genre y genre_folders no pueden tener carpetas ni géneros vacíos".to_string());
+        }
+
+        if let Some(language) = &self.audio_language {
+            if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(format!("audio_language debe ser un código de idioma (ej. \"ja\", \"pt-BR\"): {:?}", language));
+            }
+        }
+
+        // Cada regla es "contenedor" u "origen>contenedor", separadas por "/"
+        if let Some(remux) = &self.remux_container {
+            let valid = remux.split('/').all(|rule| {
+                let target = rule.rsplit('>').next().unwrap_or(rule).trim();
+                REMUX_CONTAINERS.contains(&target)
+            });
+            if !valid {
+                return Err(format!(
+                    "remux_container debe ser uno de {} (o reglas como \"webm>ogg/mka\"): {:?}",
+                    REMUX_CONTAINERS.join(", "),
+                    remux
+                ));
+            }
+        }
+
+        if let Some(id) = &self.audio_format_id {
+            if id.is_empty() || id.chars().any(|c| c.is_whitespace() || c == '/' || c == '+') {
+                return Err(format!("audio_format_id debe ser un solo id de `yt-dlp -F` (ej. \"251\"): {:?}", id));
+            }
+        }
+
+        // yt-dlp los recibe separados por comas
+        if let Some(lang) = self
+            .subtitle_langs
+            .iter()
+            .find(|lang| lang.is_empty() || lang.contains(',') || lang.chars().any(char::is_whitespace))
+        {
+            return Err(format!("subtitle_langs: idioma inválido {:?} (ej. \"es\", \"en\")", lang));
+        }
+
+        if !self.metadata_base_url.starts_with("http://") && !self.metadata_base_url.starts_with("https://") {
+            return Err(format!(
+                "metadata_base_url debe empezar con http:// o https://: {:?}",
+                self.metadata_base_url
+            ));
+        }
+
+        if self.max_concurrent_downloads == 0 {
+            return Err("max_concurrent_downloads debe ser al menos 1".to_string());
+        }
+
+        if self.max_concurrent_per_host == 0 {
+            return Err("max_concurrent_per_host debe ser al menos 1".to_string());
+        }
+
+        if self.max_concurrent_metadata == 0 {
+            return Err("max_concurrent_metadata debe ser al menos 1".to_string());
+        }
+
+        if self.session_max_downloads == Some(0) || self.session_max_mib == Some(0) {
+            return Err("session_max_downloads y session_max_mib deben ser al menos 1 (o no estar)".to_string());
+        }
+
+        if self.watch_interval_secs == 0 {
+            return Err("watch_interval_secs debe ser al menos 1".to_string());
+        }
+
+        if self.playlist_page_size == 0 {
+            return Err("playlist_page_size debe ser al menos 1".to_string());
+        }
+
+        if let Some(args) = &self.extra_yt_dlp_args {
+            if shlex::split(args).is_none() {
+                return Err(format!("extra_yt_dlp_args tiene comillas sin cerrar: {:?}", args));
+            }
+        }
+
+        if self.thumbnail_max_width == Some(0) {
+            return Err("thumbnail_max_width debe ser mayor que 0".to_string());
+        }
+
+        if !(MIN_UI_REFRESH_MS..=MAX_UI_REFRESH_MS).contains(&self.ui_refresh_ms) {
+            return Err(format!(
+                "ui_refresh_ms debe estar entre {} y {}: {}",
+                MIN_UI_REFRESH_MS, MAX_UI_REFRESH_MS, self.ui_refresh_ms
+            ));
+        }
+
+        if tracing::Level::from_str(&self.log_level).is_err() {
+            return Err(format!("log_level inválido: {:?}", self.log_level));
+        }
+
+        Ok(())
+    }
+
+    pub fn library_layout(&self) -> LibraryLayout {
+        LibraryLayout {
+            naming: self.naming_scheme,
+            shards: self.library_shards,
+            keep_original_name: self.keep_original_name,
+            artist_prefix: self.artist_prefix,
+            safe_paths: self.safe_paths,
+        }
+    }
+
+    // Título sin el ruido configurado y con feat./remix unificados (lo que esté activado)
+    pub fn clean_title(&self, title: &str) -> String {
+        let mut title = title.to_string();
+        if self.strip_title_noise {
+            let patterns: Vec<Regex> = title_noise::compile(&self.title_noise_patterns).unwrap_or_default();
+            title = title_noise::strip_noise(&title, &patterns);
+        }
+        if self.normalize_title_tags {
+            let rules = title_tags::compile(&self.title_tag_rules).unwrap_or_default();
+            title = title_tags::normalize(&title, &rules);
+        }
+        title
+    }
+
+    // Opciones de ffmpeg para la frecuencia, los canales y la normalización elegidos
+    pub fn audio_ffmpeg_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.normalize_audio {
+            args.push("-af".to_string());
+            args.push("loudnorm".to_string());
+        }
+        if let Some(rate) = self.audio_sample_rate {
+            args.push("-ar".to_string());
+            args.push(rate.to_string());
+        }
+        if let Some(channels) = self.audio_channels {
+            args.push("-ac".to_string());
+            args.push(channels.to_string());
+        }
+        args
+    }
+
+    // Ya validados en `validate()`, así que el split no falla
+    pub fn extra_yt_dlp_args(&self) -> Vec<String> {
+        self.extra_yt_dlp_args
+            .as_deref()
+            .and_then(shlex::split)
+            .unwrap_or_default()
+    }
+}
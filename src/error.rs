@@ -0,0 +1,165 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::PendingMove;
+
+// Por qué falló una descarga; el worker y la interfaz deciden según la variante (reintentar, avisar, código de salida)
+#[derive(Debug)]
+pub enum DownloadError {
+    // yt-dlp o ffmpeg no están en `libs` o no se pueden ejecutar
+    BinaryMissing(String),
+    // Se le pasó el ffmpeg de `libs` con --ffmpeg-location y aun así no lo pudo usar
+    FfmpegUnusable(PathBuf),
+    // Sin conexión, DNS, conexión cortada
+    Network(String),
+    YtDlpFailed { code: Option<i32>, stderr: String },
+    // Bloqueado en la región con geo_bypass apagado; guarda la última línea de yt-dlp
+    GeoRestricted(String),
+    // Privado, eliminado, solo para miembros
+    Unavailable(&'static str),
+    Metadata(String),
+    // El audio_format_id pedido no está entre los formatos del video
+    UnknownFormat(String),
+    // El mejor audio disponible no llega a min_audio_bitrate
+    LowBitrate { kbps: f64, min: u32 },
+    // yt-dlp terminó bien pero no está el archivo que tenía que dejar
+    MissingFile(String),
+    // Duplicado probable descartado; guarda el archivo que ya estaba
+    Duplicate(PathBuf),
+    // Canal fuera de channel_allowlist que nadie aceptó
+    ChannelNotAllowed(String),
+    // El destino no acepta archivos (pendrive con la traba de escritura, permisos)
+    NotWritable { dest_dir: PathBuf, error: io::Error },
+    Move(String),
+    // La descarga terminó pero no se pudo mover; los archivos quedan en `output` para reintentar solo el movimiento
+    MoveFailed(Box<PendingMove>, Box<DownloadError>),
+    DiskFull(String),
+    // Con varios destinos: `missing` se quedó sin el archivo; `primary` es la ruta en el principal si llegó
+    Incomplete { primary: Option<PathBuf>, missing: Vec<PathBuf> },
+    Cancelled,
+    Timeout,
+}
+
+impl DownloadError {
+    // Lo que vale la pena volver a descargar sin cambiar nada; el resto no se arregla reintentando
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            DownloadError::Network(_)
+                | DownloadError::YtDlpFailed { .. }
+                | DownloadError::Metadata(_)
+                | DownloadError::MissingFile(_)
+                | DownloadError::Timeout
+        )
+    }
+
+    // Clasifica la salida de un yt-dlp que terminó mal
+    pub fn from_yt_dlp(code: Option<i32>, stderr: &str) -> Self {
+        let lower = stderr.to_lowercase();
+        let last_line = || stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string();
+
+        if lower.contains("no space left on device") || lower.contains("not enough space on the disk") {
+            DownloadError::DiskFull(last_line())
+        } else if lower.contains("timed out") {
+            DownloadError::Timeout
+        } else if lower.contains("unable to download webpage")
+            || lower.contains("getaddrinfo failed")
+            || lower.contains("name or service not known")
+            || lower.contains("connection reset")
+            || lower.contains("network is unreachable")
+        {
+            DownloadError::Network(last_line())
+        } else {
+            DownloadError::YtDlpFailed { code, stderr: stderr.to_string() }
+        }
+    }
+
+    // Errores de disco al preparar carpetas o mover archivos
+    pub fn from_io(error: io::Error, context: String) -> Self {
+        match error.kind() {
+            io::ErrorKind::StorageFull => DownloadError::DiskFull(format!("{}: {}", context, error)),
+            io::ErrorKind::TimedOut => DownloadError::Timeout,
+            _ => DownloadError::Move(format!("{}: {}", context, error)),
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::BinaryMissing(message) => write!(f, "{}", message),
+            DownloadError::FfmpegUnusable(path) => write!(
+                f,
+                "yt-dlp no pudo usar ffmpeg en {:?}; si está dañado, bórralo para que se vuelva a descargar al iniciar",
+                path
+            ),
+            DownloadError::Network(message) => write!(f, "Error de red: {}", message),
+            DownloadError::YtDlpFailed { code, stderr } => {
+                write!(f, "Error: yt-dlp terminó con un código no exitoso {:?}", code)?;
+                match stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+                    Some(line) => write!(f, ": {}", line.trim()),
+                    None => Ok(()),
+                }
+            }
+            DownloadError::GeoRestricted(line) => write!(
+                f,
+                "{} (el video parece bloqueado en tu región; prueba activar `geo_bypass` en config.toml)",
+                line
+            ),
+            DownloadError::Unavailable(reason) => write!(f, "{}", reason),
+            DownloadError::Metadata(message) => write!(f, "Error al obtener la metadata: {}", message),
+            DownloadError::UnknownFormat(message) => write!(f, "{}", message),
+            DownloadError::LowBitrate { kbps, min } => {
+                write!(f, "audio de {:.0} kbps, por debajo del mínimo de {} kbps", kbps, min)
+            }
+            DownloadError::MissingFile(message) => write!(f, "{}", message),
+            DownloadError::Duplicate(existing) => write!(f, "Duplicado de {:?}, no se guarda", existing),
+            DownloadError::ChannelNotAllowed(channel) => write!(f, "El canal {} no está en channel_allowlist", channel),
+            DownloadError::NotWritable { dest_dir, error } => match error.kind() {
+                io::ErrorKind::ReadOnlyFilesystem | io::ErrorKind::PermissionDenied => {
+                    write!(f, "El destino {} es de solo lectura ({})", dest_dir.display(), error)
+                }
+                _ => write!(f, "No se puede escribir en el destino {}: {}", dest_dir.display(), error),
+            },
+            DownloadError::Move(message) => write!(f, "{}", message),
+            DownloadError::MoveFailed(_, error) => write!(f, "Error al mover el archivo: {}", error),
+            DownloadError::DiskFull(message) => write!(f, "Disco lleno: {}", message),
+            DownloadError::Incomplete { missing, .. } => write!(f, "Falta copiar el archivo a {:?}", missing),
+            DownloadError::Cancelled => write!(f, "Descarga cancelada"),
+            DownloadError::Timeout => write!(f, "Se agotó el tiempo de espera"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_yt_dlp_failures() {
+        let full = DownloadError::from_yt_dlp(Some(1), "ERROR: [Errno 28] No space left on device");
+        assert!(matches!(full, DownloadError::DiskFull(_)));
+        assert!(!full.is_retryable());
+
+        let offline = DownloadError::from_yt_dlp(Some(1), "ERROR: Unable to download webpage: <urlopen error>");
+        assert!(matches!(offline, DownloadError::Network(_)));
+        assert!(offline.is_retryable());
+
+        let other = DownloadError::from_yt_dlp(Some(1), "WARNING: algo\nERROR: Requested format is not available\n");
+        assert_eq!(
+            other.to_string(),
+            "Error: yt-dlp terminó con un código no exitoso Some(1): ERROR: Requested format is not available"
+        );
+    }
+
+    #[test]
+    fn skips_and_unavailable_videos_are_not_retried() {
+        assert!(!DownloadError::Unavailable("el video es privado").is_retryable());
+        assert!(!DownloadError::LowBitrate { kbps: 48.0, min: 128 }.is_retryable());
+        assert!(!DownloadError::Duplicate(PathBuf::from("musica/tema.mp3")).is_retryable());
+        assert!(DownloadError::MissingFile("No se encontró el archivo descargado en output".to_string()).is_retryable());
+    }
+}
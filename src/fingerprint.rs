@@ -0,0 +1,126 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::history::HistoryEntry;
+
+// Con menos del 15% de bits distintos se considera el mismo audio
+pub const DUPLICATE_THRESHOLD: f64 = 0.85;
+
+// fpcalc genera ~8 valores por segundo; se prueban desfasajes de hasta ~10 s (silencios, intros)
+const MAX_OFFSET: usize = 80;
+
+#[derive(Deserialize)]
+struct FpcalcOutput {
+    fingerprint: Vec<u32>,
+}
+
+// fpcalc (Chromaprint) en `libs`, o el que haya en el PATH
+fn fpcalc_binary() -> PathBuf {
+    let bundled = env::current_dir().unwrap_or_default().join("libs").join("fpcalc.exe");
+    if bundled.exists() {
+        bundled
+    } else {
+        PathBuf::from("fpcalc")
+    }
+}
+
+// Huella sin comprimir de los primeros dos minutos (lo que fpcalc analiza por defecto)
+pub async fn compute(path: &Path) -> Result<Vec<u32>, String> {
+    let output = Command::new(fpcalc_binary())
+        .arg("-raw")
+        .arg("-json")
+        .arg(path)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("No se pudo ejecutar fpcalc: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "fpcalc falló: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let parsed: FpcalcOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Salida de fpcalc inválida: {}", e))?;
+    if parsed.fingerprint.is_empty() {
+        return Err("fpcalc no devolvió ninguna huella".into());
+    }
+    Ok(parsed.fingerprint)
+}
+
+fn aligned_similarity(a: &[u32], b: &[u32]) -> Option<f64> {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return None;
+    }
+    let different: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+    Some(1.0 - different as f64 / (len as f64 * 32.0))
+}
+
+// 1.0 = iguales; dos audios sin relación rondan 0.5
+pub fn similarity(a: &[u32], b: &[u32]) -> f64 {
+    // Se exige que se solapen al menos la mitad de la huella más corta
+    let min_overlap = a.len().min(b.len()) / 2;
+    let mut best = 0.0f64;
+    for offset in 0..=MAX_OFFSET {
+        for (x, y) in [(a, b), (b, a)] {
+            if offset >= x.len() || x.len() - offset < min_overlap {
+                continue;
+            }
+            if let Some(score) = aligned_similarity(&x[offset..], y) {
+                best = best.max(score);
+            }
+        }
+    }
+    best
+}
+
+// La entrada del historial que más se parece, si pasa el umbral
+pub fn find_duplicate<'a>(fingerprint: &[u32], entries: &'a [HistoryEntry]) -> Option<(&'a HistoryEntry, f64)> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let stored = entry.fingerprint.as_deref()?;
+            Some((entry, similarity(fingerprint, stored)))
+        })
+        .filter(|(_, score)| *score >= DUPLICATE_THRESHOLD)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Valores pseudoaleatorios reproducibles
+    fn noise(seed: u32, len: usize) -> Vec<u32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_shifted_copies_only() {
+        let original = noise(1, 900);
+
+        assert_eq!(similarity(&original, &original), 1.0);
+
+        // Misma canción con 3 s más de intro y una pequeña diferencia de codificación
+        let mut shifted = noise(7, 24);
+        shifted.extend(original.iter().map(|value| value ^ 0b1));
+        assert!(similarity(&original, &shifted) >= DUPLICATE_THRESHOLD);
+        assert!(similarity(&shifted, &original) >= DUPLICATE_THRESHOLD);
+
+        assert!(similarity(&original, &noise(2, 900)) < DUPLICATE_THRESHOLD);
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+pub const HISTORY_FILE: &str = "history.json";
+
+// Evita que dos escrituras concurrentes pisen el archivo
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub path: PathBuf,
+    // Carpeta destino elegida al descargar (raíz de la biblioteca)
+    pub dest_root: PathBuf,
+    pub artist: String,
+    pub title: String,
+    pub bytes: u64,
+    // Solo para temas de álbumes de YouTube Music
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub track_number: Option<u32>,
+    // Motivo por el que el enlace no se pudo descargar (video privado, eliminado...)
+    #[serde(default)]
+    pub error: Option<String>,
+    // Huella de Chromaprint, solo si está activada la detección de duplicados
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Vec<u32>>,
+    // Segundos desde UNIX_EPOCH
+    pub downloaded_at: u64,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("No se pudo leer el historial {:?}: {}", path, e))?;
+
+    serde_json::from_str(&raw).map_err(|e| format!("Historial inválido en {:?}: {}", path, e))
+}
+
+pub fn save(path: &Path, entries: &[HistoryEntry]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+
+    std::fs::write(path, json).map_err(|e| format!("No se pudo guardar el historial {:?}: {}", path, e))
+}
+
+pub fn record(path: &Path, entry: HistoryEntry) -> Result<(), String> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+
+    let mut entries = load(path)?;
+    entries.push(entry);
+    save(path, &entries)
+}
+
+// Saca la entrada más reciente de ese archivo; devuelve si la encontró
+pub fn remove(path: &Path, entry_path: &Path) -> Result<bool, String> {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+
+    let mut entries = load(path)?;
+    let Some(index) = entries.iter().rposition(|entry| entry.path == entry_path) else {
+        return Ok(false);
+    };
+    entries.remove(index);
+    save(path, &entries)?;
+    Ok(true)
+}
@@ -0,0 +1,37 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use tracing::Level;
+use tracing::level_filters::LevelFilter;
+
+pub const LOG_FILE: &str = "gif_tube_desk.log";
+
+// Cada -v sube un nivel sobre el configurado (info -> debug -> trace)
+pub fn effective_level(configured: &str, verbose: u8) -> Level {
+    let base = Level::from_str(configured).unwrap_or(Level::INFO);
+
+    match verbose {
+        0 => base,
+        1 if base > Level::DEBUG => base,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+// Con la TUI activa stdout está ocupado por la pantalla alternativa, así que se escribe a archivo
+pub fn init(level: LevelFilter, to_file: bool) -> io::Result<()> {
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false);
+
+    if to_file {
+        let file = OpenOptions::new().create(true).append(true).open(LOG_FILE)?;
+        builder.with_ansi(false).with_writer(Mutex::new(file)).init();
+    } else {
+        builder.with_writer(io::stderr).init();
+    }
+
+    Ok(())
+}
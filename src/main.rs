@@ -1,88 +1,375 @@
+mod bookmarks;
+mod cli;
+mod config;
+mod error;
+mod fingerprint;
+mod history;
+mod logging;
+mod manifest;
+mod metadata_cache;
+mod queue;
+mod settings;
+mod sidecar;
+mod stats;
+mod status;
+mod tags;
+mod title_noise;
+mod title_tags;
+mod youtube_url;
+
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
-use tokio::sync::mpsc as tokio_mpsc;
+use tokio::sync::Semaphore;
+use tokio::task::{JoinError, JoinSet};
 
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::sync::mpsc::{self, Receiver};
-use std::time::Duration;
-use std::io::{self};
+use std::time::{Duration, Instant, SystemTime};
+use std::io::{self, IsTerminal, Write};
+use std::fmt;
 use std::env;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use regex::Regex;
 
+use notify::{RecursiveMode, Watcher};
+
 use yt_dlp::Youtube;
 use yt_dlp::fetcher::deps::Libraries;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+
+use tracing::{debug, error, info, warn};
+use tracing::level_filters::LevelFilter;
+
+use clap::Parser;
 
-//use sysinfo::{Disks, System};
+use cli::{Cli, EXIT_BAD_ARGUMENTS, EXIT_PARTIAL_FAILURE, EXIT_STARTUP_ERROR, EXIT_TOTAL_FAILURE};
+use config::{
+    ArtistPrefix, Config, ContentKind, DuplicatePolicy, LibraryLayout, NamingScheme, PlaylistErrorPolicy, ProgressStyle, ShardScheme, ThumbnailFit, ThumbnailFormat, CONFIG_FILE,
+};
+use error::DownloadError;
+use history::{HistoryEntry, HISTORY_FILE};
+use stats::STATS_FILE;
+use metadata_cache::{MetadataCache, METADATA_CACHE_FILE};
+use queue::{DownloadQueue, ItemState, QueueItem, QUEUE_FILE};
+use settings::{cycle_audio_format, step_audio_quality, SettingsForm};
+use sidecar::SidecarData;
+use tags::TrackTags;
+use status::{BatchProgress, ChannelPrompt, CollisionChoice, CollisionPrompt, StatusEvent, StatusSender};
+use youtube_url::{host_key, parse_media_url, parse_youtube_url, queue_key, MediaUrl, YoutubeUrl};
+
+use sysinfo::{Disks, Pid, ProcessesToUpdate, System};
 
 use crossterm::{
-  event::{self, Event, KeyCode, KeyModifiers, KeyEventKind},
+  event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton,
+    MouseEventKind,
+  },
   execute,
   terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
 use tui::{
   backend::CrosstermBackend,
-  layout::{Constraint, Direction, Layout},
+  layout::{Constraint, Direction, Layout, Rect},
   style::{Color, Modifier, Style},
   text::{Span, Spans},
-  widgets::{Block, Borders, Paragraph},
+  widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
   Terminal,
 };
 
-//#[derive(Debug)]
-//struct Disk {
-//    name: String,
-//    total: u64,
-//    free: u64,
-//    used: u64,
-//    used_percent: f64,
-//    address: String,
-//}
-
-#[derive(Deserialize, Debug)]
+#[derive(Debug)]
+struct Disk {
+    name: String,
+    total: u64,
+    free: u64,
+    used: u64,
+    used_percent: f64,
+    address: String,
+    mount_point: PathBuf,
+}
+
+impl Disk {
+    fn summary(&self) -> String {
+        format!(
+            "Disco {} ({}): libre {} de {} ({} usado, {:.1}%)",
+            self.name,
+            self.address,
+            format_bytes(self.free),
+            format_bytes(self.total),
+            format_bytes(self.used),
+            self.used_percent
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct VideoMetadata {
     title: String,
     author_name: String,
+    // Presentes solo para temas de un álbum; oembed no los devuelve
+    #[serde(default)]
+    album: Option<String>,
+    #[serde(default)]
+    track_number: Option<u32>,
+}
+
+// Metadata completa que devuelve `yt-dlp --dump-json`
+#[derive(Deserialize, Debug, Clone)]
+struct VideoInfo {
+    title: String,
+    #[serde(default)]
+    uploader: Option<String>,
+    #[serde(default)]
+    channel: Option<String>,
+    #[serde(default)]
+    channel_id: Option<String>,
+    #[serde(default)]
+    uploader_id: Option<String>,
+    #[serde(default)]
+    duration: Option<f64>,
+    // Campos de YouTube Music (temas "- Topic")
+    #[serde(default)]
+    album: Option<String>,
+    #[serde(default)]
+    artist: Option<String>,
+    #[serde(default)]
+    track: Option<String>,
+    #[serde(default)]
+    track_number: Option<u32>,
+    #[serde(default)]
+    upload_date: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    formats: Vec<FormatInfo>,
+    #[serde(default)]
+    categories: Vec<String>,
+}
+
+// Una entrada de `formats` (lo mismo que lista `yt-dlp -F`)
+#[derive(Deserialize, Debug, Clone)]
+struct FormatInfo {
+    format_id: String,
+    #[serde(default)]
+    acodec: Option<String>,
+    // kbps
+    #[serde(default)]
+    abr: Option<f64>,
+    // Idioma de la pista en videos con doblajes
+    #[serde(default)]
+    language: Option<String>,
+    // yt-dlp marca la pista original con 10
+    #[serde(default)]
+    language_preference: Option<i32>,
+}
+
+impl FormatInfo {
+    fn has_audio(&self) -> bool {
+        self.acodec.as_deref().is_some_and(|codec| codec != "none")
+    }
 }
 
-//async fn get_disk_info() -> Result<Vec<Disk>, String> {
-//    let mut sys = System::new_all();
+impl VideoInfo {
+    // Bitrate (kbps) del mejor audio que ofrece el sitio; None si yt-dlp no lo informa
+    fn best_audio_bitrate(&self) -> Option<f64> {
+        self.formats
+            .iter()
+            .filter(|format| format.has_audio())
+            .filter_map(|format| format.abr)
+            .max_by(f64::total_cmp)
+    }
+
+    // Heurística para detect_speech: largo y sin señales de ser música
+    fn looks_like_speech(&self, min_minutes: u64) -> bool {
+        let is_music = self.track.is_some() || self.categories.iter().any(|category| category == "Music");
+        !is_music && self.duration.is_some_and(|secs| secs >= (min_minutes * 60) as f64)
+    }
+
+    // Idiomas de audio (idioma, es el original), la original primero; vacío si el video no trae datos de idioma
+    fn audio_languages(&self) -> Vec<(String, bool)> {
+        let mut languages: Vec<(String, bool)> = Vec::new();
+        for format in self.formats.iter().filter(|format| format.has_audio()) {
+            let Some(language) = &format.language else {
+                continue;
+            };
+            let original = format.language_preference.is_some_and(|preference| preference >= 10);
+            match languages.iter_mut().find(|(known, _)| known == language) {
+                Some(entry) => entry.1 |= original,
+                None => languages.push((language.clone(), original)),
+            }
+        }
+        languages.sort_by_key(|(_, original)| !original);
+        languages
+    }
+
+    // "es (original), en, ja"
+    fn audio_languages_label(&self) -> String {
+        self.audio_languages()
+            .iter()
+            .map(|(language, original)| if *original { format!("{} (original)", language) } else { language.clone() })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn author(&self) -> &str {
+        self.uploader
+            .as_deref()
+            .or(self.channel.as_deref())
+            .unwrap_or("Desconocido")
+    }
+
+    // Todo lo que puede figurar en channel_allowlist: nombres e ids del canal
+    fn channel_names(&self) -> Vec<String> {
+        [&self.uploader, &self.channel, &self.channel_id, &self.uploader_id]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    // Metadata para ordenar el tema por álbum; None si yt-dlp no conoce el álbum
+    fn album_metadata(&self) -> Option<VideoMetadata> {
+        let album = self.album.clone()?;
+        // El canal "Artista - Topic" es el último recurso para el artista
+        let author_name = self
+            .artist
+            .clone()
+            .unwrap_or_else(|| self.author().trim_end_matches(" - Topic").to_string());
+
+        Some(VideoMetadata {
+            title: self.track.clone().unwrap_or_else(|| self.title.clone()),
+            author_name,
+            album: Some(album),
+            track_number: self.track_number,
+        })
+    }
+
+    fn track_tags(&self, config: &Config) -> TrackTags {
+        let artist = self
+            .artist
+            .clone()
+            .unwrap_or_else(|| self.author().trim_end_matches(" - Topic").to_string());
+
+        TrackTags {
+            title: config.clean_title(self.track.as_deref().unwrap_or(&self.title)),
+            artist,
+            album: self.album.clone(),
+            track: self.track_number,
+            year: self
+                .upload_date
+                .as_deref()
+                .and_then(|date| date.get(0..4))
+                .and_then(|year| year.parse().ok()),
+        }
+    }
+
+    fn sidecar_data(&self, url: &str, metadata: &VideoMetadata) -> SidecarData {
+        SidecarData {
+            title: metadata.title.clone(),
+            artist: metadata.author_name.clone(),
+            album: metadata.album.clone(),
+            track_number: metadata.track_number,
+            upload_date: self.upload_date.as_deref().and_then(sidecar::format_upload_date),
+            duration: self.duration_secs(),
+            url: url.to_string(),
+            description: self.description.clone().filter(|d| !d.trim().is_empty()),
+        }
+    }
+
+    // Los directos y algunos sitios devuelven 0 o nada
+    fn duration_secs(&self) -> Option<u64> {
+        self.duration
+            .filter(|secs| secs.is_finite() && *secs >= 1.0)
+            .map(|secs| secs.round() as u64)
+    }
+
+    fn duration_label(&self) -> String {
+        match self.duration_secs() {
+            Some(secs) => format_duration(secs),
+            None => "desconocida".to_string(),
+        }
+    }
+}
 
-//    sys.refresh_all();
+// Unidades extraíbles (pendrives, tarjetas) con su espacio
+fn get_disk_info() -> Result<Vec<Disk>, String> {
+    let mut disks: Vec<Disk> = Vec::new();
 
-//    let mut disks: Vec<Disk> = Vec::new();
-/*
     for disk in Disks::new_with_refreshed_list().list() {
         if disk.is_removable() {
             let name = disk.name().to_string_lossy().into_owned();
             let mount_point = disk.mount_point().to_path_buf();
             let fs = disk.file_system().to_string_lossy().to_string();
             let address = format!("{}:{}", mount_point.to_string_lossy(), fs);
+
+            let total = disk.total_space();
+            let free = disk.available_space();
+            let used = total.saturating_sub(free);
+            let used_percent = if total > 0 { used as f64 * 100.0 / total as f64 } else { 0.0 };
+
             disks.push(Disk {
                 name,
-                total: 0,
-                free: 0,
-                used: 0,
+                total,
+                free,
+                used,
                 address,
-                used_percent: 0.0,
+                used_percent,
+                mount_point,
             });
         }
     }
 
-    return if disks.is_empty() {
+    if disks.is_empty() {
         Err("No se encontraron discos".to_string())
     } else {
-        disks
+        Ok(disks)
+    }
+}
+
+// --list-disks: para elegir --dest sin abrir la interfaz
+fn run_list_disks() -> Result<(), String> {
+    let disks = get_disk_info()?;
+    let name_width = disks.iter().map(|disk| disk.name.chars().count()).max().unwrap_or(0).max("Nombre".len());
+    let address_width = disks.iter().map(|disk| disk.address.chars().count()).max().unwrap_or(0).max("Montaje".len());
+
+    println!("{:<name_width$}  {:<address_width$}  Libre / total", "Nombre", "Montaje");
+    for disk in &disks {
+        println!(
+            "{:<name_width$}  {:<address_width$}  {} / {}",
+            disk.name,
+            disk.address,
+            format_bytes(disk.free),
+            format_bytes(disk.total)
+        );
     }
+    Ok(())
+}
+
+// Segundos desde UNIX_EPOCH de la última actualización de yt-dlp que salió bien
+const LAST_UPDATE_FILE: &str = "libs/last_update";
+
+fn last_binaries_update() -> Option<u64> {
+    std::fs::read_to_string(LAST_UPDATE_FILE).ok()?.trim().parse().ok()
 }
-*/
 
-async fn get_or_update_yt_dlp() -> Result<(), String>{
+fn mark_binaries_updated() {
+    if let Err(e) = std::fs::write(LAST_UPDATE_FILE, history::now_secs().to_string()) {
+        warn!(error = %e, "no se pudo guardar la fecha de actualización de yt-dlp");
+    }
+}
+
+// Con binarios ya descargados se actualiza como mucho una vez cada `update_interval`,
+// y si la actualización falla (sin conexión) se sigue con los que hay
+async fn get_or_update_yt_dlp(update_interval: Duration) -> anyhow::Result<()> {
     let libraries_dir = PathBuf::from("libs");
     let output_dir = PathBuf::from("output");
 
@@ -90,42 +377,191 @@ async fn get_or_update_yt_dlp() -> Result<(), String>{
     let ffmpeg = libraries_dir.join("ffmpeg");
 
     let libraries = Libraries::new(youtube.clone(), ffmpeg.clone());
-    let fetcher: Youtube;
 
-    if !youtube.exists() || !ffmpeg.exists() {
-        println!("Descargando binarios...");
-        fetcher = Youtube::with_new_binaries(libraries_dir, &output_dir).await.unwrap();
-    }else{
+    let fetcher = if !youtube.exists() || !ffmpeg.exists() {
+        with_spinner(
+            "Descargando binarios...",
+            Youtube::with_new_binaries(libraries_dir, &output_dir),
+        )
+        .await
+        .context("No se pudieron descargar yt-dlp y ffmpeg")?;
+        // Recién bajados ya son la última versión
+        mark_binaries_updated();
+        return Ok(());
+    } else {
         println!("Binarios ya existentes");
-        fetcher = Youtube::new(libraries, output_dir).unwrap();
+        Youtube::new(libraries, output_dir).context("No se pudieron cargar los binarios de './libs'")?
+    };
+
+    let elapsed = last_binaries_update().map(|at| Duration::from_secs(history::now_secs().saturating_sub(at)));
+    if elapsed.is_some_and(|elapsed| elapsed < update_interval) {
+        debug!(?elapsed, "yt-dlp actualizado hace poco, no se vuelve a actualizar");
+        return Ok(());
     }
 
-    fetcher.update_downloader().await.unwrap();
+    match with_spinner("Actualizando yt-dlp...", fetcher.update_downloader()).await {
+        Ok(_) => mark_binaries_updated(),
+        Err(e) => {
+            warn!(error = %e, "no se pudo actualizar yt-dlp; se usan los binarios actuales");
+            eprintln!("Aviso: no se pudo actualizar yt-dlp ({}); se usan los binarios actuales", e);
+        }
+    }
     Ok(())
 }
 
+const SPINNER_FRAMES: [&str; 4] = ["|", "/", "-", "\\"];
+
+// Indicador de progreso en consola para las fases previas a la UI
+async fn with_spinner<F: Future>(label: &str, future: F) -> F::Output {
+    tokio::pin!(future);
+    let mut ticker = tokio::time::interval(Duration::from_millis(100));
+    let mut frame = 0;
+
+    loop {
+        tokio::select! {
+            output = &mut future => {
+                println!("\r{} listo", label);
+                return output;
+            }
+            _ = ticker.tick() => {
+                print!("\r{} {}", SPINNER_FRAMES[frame % SPINNER_FRAMES.len()], label);
+                let _ = io::stdout().flush();
+                frame += 1;
+            }
+        }
+    }
+}
+
+const FALLBACK_FILE_NAME: &str = "untitled";
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+
 fn sanitize_filename(name: &str) -> String {
+    sanitize_filename_or(name, FALLBACK_FILE_NAME)
+}
+
+fn sanitize_filename_or(name: &str, fallback: &str) -> String {
     let invalid_chars = Regex::new(r#"[\x00-\x1F<>:"/\\|?*]+"#).unwrap();
 
     let cleaned = invalid_chars.replace_all(name, "_");
 
-    let cleaned = cleaned.trim_matches(|c: char| c == ' ' || c == '.').to_string();
+    let is_trimmable = |c: char| c == ' ' || c == '.';
+    let cleaned = cleaned.trim_matches(is_trimmable);
 
     let max_len = 32;
-    if cleaned.len() > max_len {
-        cleaned.chars().take(max_len).collect()
+    let truncated: String = cleaned.chars().take(max_len).collect();
+
+    // Al truncar puede quedar un espacio o punto final, inválido en Windows
+    let cleaned = truncated.trim_end_matches(is_trimmable);
+
+    // Un nombre vacío o solo con '_' rompería move_audio_file()
+    if cleaned.chars().all(|c| c == '_') {
+        fallback.to_string()
     } else {
-        cleaned
+        cleaned.to_string()
+    }
+}
+
+// Un solo cliente para todas las consultas, así se reutilizan las conexiones. Se guarda con el user_agent
+// con que se armó: si cambia desde los ajustes o con Ctrl+G se arma otro
+static HTTP_CLIENT: Mutex<Option<(Option<String>, reqwest::Client)>> = Mutex::new(None);
+
+fn http_client(config: &Config) -> reqwest::Result<reqwest::Client> {
+    let mut cached = HTTP_CLIENT.lock().unwrap();
+    if let Some((user_agent, client)) = cached.as_ref() {
+        if *user_agent == config.user_agent {
+            return Ok(client.clone());
+        }
+    }
+
+    let mut builder = reqwest::Client::builder();
+    if let Some(user_agent) = &config.user_agent {
+        builder = builder.user_agent(user_agent.as_str());
+    }
+    let client = builder.build()?;
+    *cached = Some((config.user_agent.clone(), client.clone()));
+    Ok(client)
+}
+
+// Compartida por todas las tareas del worker; con cache_metadata_on_disk se inicia al arrancar con el archivo
+static METADATA_CACHE: OnceLock<Arc<MetadataCache<VideoMetadata>>> = OnceLock::new();
+
+fn metadata_cache() -> Arc<MetadataCache<VideoMetadata>> {
+    METADATA_CACHE.get_or_init(|| Arc::new(MetadataCache::new(None))).clone()
+}
+
+// Una consulta por video en la sesión: los reintentos y lo que se vuelve a encolar usan la caché
+async fn get_metadata_video(url: &str, config: &Config, tx: &StatusSender) -> Result<VideoMetadata, Box<dyn std::error::Error>> {
+    let key = queue_key(url);
+    let cache = metadata_cache();
+    if let Some(metadata) = cache.get(&key) {
+        debug!(url, "metadata desde la caché");
+        return Ok(metadata);
     }
+
+    let metadata = fetch_metadata_video(url, config, tx).await?;
+    cache.insert(key, metadata.clone());
+    Ok(metadata)
 }
 
-async fn get_metadata_video(url: &str, tx: &mpsc::Sender<String>) -> Result<VideoMetadata, Box<dyn std::error::Error>> {
+async fn fetch_metadata_video(url: &str, config: &Config, tx: &StatusSender) -> Result<VideoMetadata, Box<dyn std::error::Error>> {
+    // oembed solo existe para YouTube; para otros sitios se usa la metadata de yt-dlp
+    if parse_youtube_url(url).is_err() {
+        let info = get_metadata_json(url, config, tx).await?;
+        return Ok(VideoMetadata {
+            title: info.title.clone(),
+            author_name: info.author().to_string(),
+            album: None,
+            track_number: None,
+        });
+    }
+
     let _ = tx.send("Obteniendo metadata del video...".to_string());
-    let full_url = format!(
-        "https://www.youtube.com/oembed?url={}&format=json",
-        url
-    );
-    let resp = reqwest::get(&full_url).await?;
+    let busy = tx.busy("Obteniendo metadata del video");
+    let result = match http_client(config) {
+        Ok(client) => fetch_oembed(&client, &config.metadata_base_url, url).await,
+        Err(e) => Err(e.into()),
+    };
+    drop(busy);
+
+    // El error se pasa a texto antes de esperar a yt-dlp: Box<dyn Error> no es Send
+    match result.map_err(|e| e.to_string()) {
+        Err(e) if config.oembed_fallback_yt_dlp => {
+            let _ = tx.error(format!("Metadata fetch failed (TLS/network): {}; se pide a yt-dlp (oembed_fallback_yt_dlp)", e));
+            let info = get_metadata_json(url, config, tx).await?;
+            Ok(VideoMetadata {
+                title: info.title.clone(),
+                author_name: info.author().to_string(),
+                album: None,
+                track_number: None,
+            })
+        }
+        result => Ok(result?),
+    }
+}
+
+// Sin metadata se arma con lo que haya: la de yt-dlp si ya se pidió, si no el nombre del archivo ("Artista - Título")
+fn fallback_metadata(info: Option<&VideoInfo>, file_name: &str) -> VideoMetadata {
+    if let Some(info) = info {
+        return VideoMetadata {
+            title: info.title.clone(),
+            author_name: info.author().to_string(),
+            album: None,
+            track_number: None,
+        };
+    }
+
+    let (author_name, title, _, _) = names_from_path(Path::new(file_name));
+    VideoMetadata { title, author_name, album: None, track_number: None }
+}
+
+// Consulta `{base_url}/oembed`; el cliente y la URL base se reciben para poder usar otro servidor
+async fn fetch_oembed(client: &reqwest::Client, base_url: &str, url: &str) -> Result<VideoMetadata, Box<dyn std::error::Error>> {
+    debug!(url, base_url, "consultando oembed");
+    let resp = client
+        .get(format!("{}/oembed", base_url.trim_end_matches('/')))
+        .query(&[("url", url), ("format", "json")])
+        .send()
+        .await?;
     if !resp.status().is_success() {
         return Err(format!("HTTP error: {}", resp.status()).into());
     }
@@ -133,34 +569,138 @@ async fn get_metadata_video(url: &str, tx: &mpsc::Sender<String>) -> Result<Vide
     Ok(metadata)
 }
 
-async fn get_downloaded_file_name(output_path: &str) -> Result<Option<String>, String> {
-    match fs::read_dir(output_path).await {
-        Ok(mut dir_entries) => {
-            while let Some(entry) = dir_entries.next_entry().await.unwrap() {
-                let file_type = entry.file_type().await.unwrap();
-                if file_type.is_file() {
-                    if let Some(file_name) = entry.file_name().into_string().ok() {
-                        return Ok(Some(file_name.to_string()));
-                    }
-                }
+async fn get_metadata_json(url: &str, config: &Config, tx: &StatusSender) -> Result<VideoInfo, DownloadError> {
+    let _ = tx.send("Obteniendo metadata completa con yt-dlp...".to_string());
+    let _busy = tx.busy("Obteniendo metadata con yt-dlp");
+    debug!(url, "consultando metadata con --dump-json");
+
+    let yt_dlp_path = yt_dlp_binary(tx)?;
+
+    let output = Command::new(yt_dlp_path)
+        .arg("--dump-json")
+        .arg("--no-playlist")
+        .arg("--skip-download")
+        .args(network_args(config))
+        .arg(url)
+        .output()
+        .await
+        .map_err(|e| DownloadError::BinaryMissing(format!("No se pudo ejecutar yt-dlp: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match unavailable_reason(&stderr) {
+            Some(reason) => DownloadError::Unavailable(reason),
+            None => DownloadError::Metadata(format!("yt-dlp: {}", stderr.trim())),
+        });
+    }
+
+    serde_json::from_slice::<VideoInfo>(&output.stdout)
+        .map_err(|e| DownloadError::Metadata(format!("JSON inválido: {}", e)))
+}
+
+// Tamaño legible en unidades binarias con un decimal (1536 -> "1.5 KiB")
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn format_duration(total_secs: u64) -> String {
+    let hours = total_secs / 3600;
+    let minutes = (total_secs % 3600) / 60;
+    let seconds = total_secs % 60;
+
+    if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+// Sufijo para el nombre de archivo: ":" no es válido en Windows, así que "[03.42]" o "[01.02.03]"
+fn duration_suffix(total_secs: u64) -> String {
+    format!(" [{}]", format_duration(total_secs).replace(':', "."))
+}
+
+async fn print_metadata(url: &str, config: &Config) -> Result<(), String> {
+    // Los mensajes de estado no se muestran en este modo
+    let (tx, _rx) = status::channel();
+
+    let info = get_metadata_json(url, config, &tx).await.map_err(|e| e.to_string())?;
+
+    println!("Título:   {}", info.title);
+    println!("Autor:    {}", info.author());
+    println!("Duración: {}", info.duration_label());
+    if info.audio_languages().len() > 1 {
+        println!("Audio:    {}", info.audio_languages_label());
+    }
+    Ok(())
+}
+
+// El orden de read_dir no es fijo, así que no sirve tomar el primero: con `extension` se busca el archivo que la tiene,
+// y sin ella (se guarda el formato original) tiene que quedar uno solo que no sea un parcial
+async fn get_downloaded_file_name(output_path: &str, extension: Option<&str>) -> Result<Option<String>, String> {
+    let candidates: Vec<String> = get_downloaded_file_names(output_path)
+        .await?
+        .into_iter()
+        .filter(|name| !is_partial_file_name(name))
+        .filter(|name| extension.is_none_or(|extension| file_extension(name).eq_ignore_ascii_case(extension)))
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Ok(None),
+        [file_name] => Ok(Some(file_name.clone())),
+        many => Err(format!("Hay más de un archivo descargado en {}: {}", output_path, many.join(", "))),
+    }
+}
+
+// Ordenados por nombre
+async fn get_downloaded_file_names(output_path: &str) -> Result<Vec<String>, String> {
+    let mut dir_entries = fs::read_dir(output_path).await.map_err(|e| e.to_string())?;
+    let mut file_names = Vec::new();
+
+    while let Some(entry) = dir_entries.next_entry().await.map_err(|e| e.to_string())? {
+        if entry.file_type().await.map_err(|e| e.to_string())?.is_file() {
+            if let Ok(file_name) = entry.file_name().into_string() {
+                file_names.push(file_name);
             }
-            Err("No se encontraron archivos en el directorio de salida".into())
-        },
-        Err(e) => {
-            Err(e.to_string())
         }
     }
-}
 
+    file_names.sort();
+    Ok(file_names)
+}
 
-async fn download_audio(
-    url: &str,
+// Con `--keep-video` quedan en la carpeta el archivo original y el convertido
+async fn get_primary_and_source_names(
     output_path: &str,
     audio_format: &str,
-    audio_quality: &str,
-    tx: &mpsc::Sender<String>,
-) -> Result<PathBuf, String> {
+) -> Result<(String, Option<String>), String> {
+    let file_names = get_downloaded_file_names(output_path).await?;
+    let extension = format!(".{}", audio_extension(audio_format));
 
+    let primary = file_names
+        .iter()
+        .find(|name| name.ends_with(&extension))
+        .cloned()
+        .ok_or_else(|| format!("No se encontró un archivo {} en {}", extension, output_path))?;
+    let source = file_names.into_iter().find(|name| *name != primary && !is_partial_file_name(name));
+
+    Ok((primary, source))
+}
+
+fn yt_dlp_binary(tx: &StatusSender) -> Result<PathBuf, DownloadError> {
     let current_dir = env::current_dir().unwrap();
 
     let root_path = current_dir.join("libs");
@@ -170,222 +710,3645 @@ async fn download_audio(
     let _ = tx.send(format!("binario a buscar: {:?}", yt_dlp_path));
 
     if !yt_dlp_path.exists() {
-        return Err("El binario yt-dlp no se encuentra en la carpeta './libs'.".into());
+        return Err(DownloadError::BinaryMissing(
+            "El binario yt-dlp no se encuentra en la carpeta './libs'.".to_string(),
+        ));
     }
 
-    let output_template = format!("{}/%(title)s.%(ext)s", output_path);
+    Ok(yt_dlp_path)
+}
 
-    let mut child = Command::new(yt_dlp_path)
-        .arg("--extract-audio")
-        .arg("--audio-format")
-        .arg(audio_format)
-        .arg("--audio-quality")
-        .arg(audio_quality)
-        .arg("-o")
-        .arg(&output_template)
-        .arg(url)
-        .spawn().unwrap();
+fn ffmpeg_binary() -> Result<PathBuf, DownloadError> {
+    let ffmpeg_path = env::current_dir().unwrap().join("libs").join("ffmpeg.exe");
 
-    let status = child.wait().await.unwrap();
-    if !status.success() {
-        return Err(format!(
-            "Error: yt-dlp terminó con un código no exitoso {:?}",
-            status.code()
-        )
-        .into());
+    if !ffmpeg_path.exists() {
+        return Err(DownloadError::BinaryMissing(
+            "El binario ffmpeg no se encuentra en la carpeta './libs'.".to_string(),
+        ));
     }
 
-    let _ = tx.send(format!("Audio descargado correctamente en: {}", output_path));
+    Ok(ffmpeg_path)
+}
 
-    Ok(PathBuf::from(output_path))
+// Códec de ffmpeg y extensión resultante para cada formato de audio
+fn audio_codec(audio_format: &str) -> Option<(&'static str, &'static str)> {
+    match audio_format {
+        "mp3" => Some(("libmp3lame", "mp3")),
+        "aac" | "m4a" => Some(("aac", "m4a")),
+        "alac" => Some(("alac", "m4a")),
+        "flac" => Some(("flac", "flac")),
+        "opus" => Some(("libopus", "opus")),
+        "vorbis" => Some(("libvorbis", "ogg")),
+        "wav" => Some(("pcm_s16le", "wav")),
+        _ => None,
+    }
 }
 
-async fn move_audio_file(
-    src_dir: &Path,
-    dest_dir: &Path,
-    file_name: &str,
-    metadata: &VideoMetadata,
-    tx: &mpsc::Sender<String>,
-) -> Result<(), String> {
+fn audio_extension(audio_format: &str) -> &str {
+    audio_codec(audio_format).map(|(_, ext)| ext).unwrap_or(audio_format)
+}
 
-    let mut dest_dir = dest_dir.to_path_buf();
+// Extensión del audio que deja yt-dlp; None si se guarda el códec original (o solo se cambia el contenedor)
+fn downloaded_extension(config: &Config) -> Option<&str> {
+    config.reencodes().then(|| audio_extension(config.effective_audio_format()))
+}
 
-    dest_dir.push(sanitize_filename(metadata.author_name.as_str()));
-    
-    if !dest_dir.exists() {
-        let _ = tx.send(format!("La ruta {:?} no existe; créala o revisa el path", &dest_dir));
-        match fs::create_dir_all(&dest_dir).await {
-            Ok(_) => {
-                let _ = tx.send(format!("Directorio creado exitosamente: {:?}", &dest_dir));
-            },
-            Err(e) => {
-                return Err(format!("Error al crear el directorio de destino: {:?}", e));
-            }
-        }
-    }
+async fn convert_audio(
+    source: &Path,
+    convert_dir: &str,
+    audio_format: &str,
+    audio_quality: &str,
+    ffmpeg_args: &[String],
+    tx: &StatusSender,
+) -> Result<PathBuf, String> {
+    let (codec, extension) = audio_codec(audio_format)
+        .ok_or_else(|| format!("Formato de audio no soportado: {}", audio_format))?;
 
-    let source_path = src_dir.join(file_name);
+    let ffmpeg_path = ffmpeg_binary().map_err(|e| e.to_string())?;
 
-    let dest_path: PathBuf;
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| FALLBACK_FILE_NAME.to_string());
+    let target = Path::new(convert_dir).join(format!("{}.{}", stem, extension));
 
-    if metadata.title.as_str().contains(metadata.author_name.as_str()) {
-        dest_path = dest_dir
-            .join(format!(
-                "{}.{}",
-                sanitize_filename(metadata.title.as_str()),
-                file_name.split('.').last().unwrap_or("mp3")
-            ));
-    } else {
-        dest_path = dest_dir
-            .join(format!(
-                "{}-{}.{}",
-                sanitize_filename(metadata.author_name.as_str()),
-                sanitize_filename(metadata.title.as_str()),
-                file_name.split('.').last().unwrap_or("mp3")
-            ));
+    let mut command = Command::new(ffmpeg_path);
+    command
+        .kill_on_drop(true)
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(source)
+        .arg("-vn")
+        .arg("-c:a")
+        .arg(codec);
+
+    // La calidad VBR de yt-dlp (0-9) coincide con la escala de LAME
+    if codec == "libmp3lame" && audio_quality.parse::<u8>().is_ok() {
+        command.arg("-q:a").arg(audio_quality);
     }
+    command.args(ffmpeg_args);
 
-    if dest_path.exists() {
-        let _ = tx.send(format!(
-            "El archivo '{}' ya existe en el destino. Moviendo con un nuevo nombre...",
-            file_name
+    let output = command
+        .arg(&target)
+        .output()
+        .await
+        .map_err(|e| format!("No se pudo ejecutar ffmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg falló al convertir a {}: {}",
+            audio_format,
+            String::from_utf8_lossy(&output.stderr).trim()
         ));
-        
-        let mut counter = 1;
-        let mut new_dest_path = dest_path.clone();
-        while new_dest_path.exists() {
-            if metadata.title.as_str().contains(metadata.author_name.as_str()) {
-                let new_name = format!(
-                    "{}_{}.{}",
-                    sanitize_filename(metadata.title.as_str()),
-                    counter,
-                    file_name.split('.').last().unwrap_or("mp3")
-                );
-                new_dest_path = dest_dir.join(new_name);
-                counter += 1;
-            } else {
-                let new_name = format!(
-                    "{}-{}_{}.{}",
-                    sanitize_filename(metadata.author_name.as_str()),
-                    sanitize_filename(metadata.title.as_str()),
-                    counter,
-                    file_name.split('.').last().unwrap_or("mp3")
-                );
-                new_dest_path = dest_dir.join(new_name);
-                counter += 1;
-            }
-        }
-        fs::copy(&source_path, new_dest_path).await.unwrap();
-        fs::remove_file(&source_path).await.unwrap();
-    } else {
-        fs::copy(&source_path, dest_path).await.unwrap();
-        fs::remove_file(&source_path).await.unwrap();
     }
 
-    let _ = tx.send(format!("Archivo movido a: {:?}", dest_dir));
-    Ok(())
+    let _ = tx.send(format!("Convertido a {}: {:?}", audio_format, target));
+
+    Ok(target)
 }
 
-async fn download(url: &str, dest_dir: &str, tx: &mpsc::Sender<String>) -> Result<(), String> {
-    let output_dir = "output";
-    let audio_format = "mp3";
-    let audio_quality = "0";
+// Argumentos de red comunes a todas las invocaciones de yt-dlp
+fn network_args(config: &Config) -> Vec<String> {
+    let mut args = Vec::new();
 
-    if !Path::new(output_dir).exists() {
-        if let Err(e) = fs::create_dir_all(output_dir).await {
-            let _ = tx.send(format!("Error al crear el directorio de salida: {}", e));
-            return Ok(());
+    if config.geo_bypass {
+        args.push("--geo-bypass".to_string());
+        if let Some(country) = &config.geo_bypass_country {
+            args.push("--geo-bypass-country".to_string());
+            args.push(country.clone());
         }
     }
 
-    if !Path::new(dest_dir).exists() {
-        if let Err(e) = fs::create_dir_all(dest_dir).await {
-            let _ = tx.send(format!("Error al crear el directorio destino: {}", e));
-            return Ok(());
-        }
+    if let Some(user_agent) = &config.user_agent {
+        args.push("--user-agent".to_string());
+        args.push(user_agent.clone());
     }
 
-    match download_audio(url, output_dir, audio_format, audio_quality, tx).await {
-        Ok(download_path) => {
-            let file_name = get_downloaded_file_name(output_dir).await?.unwrap();
-            let _ = tx.send(format!("File name: {}", file_name));
+    // Reintentos dentro de la misma ejecución, antes de que download_retries vuelva a empezar la descarga
+    args.push("--retries".to_string());
+    args.push(config.network_retries.to_string());
+    args.push("--fragment-retries".to_string());
+    args.push(config.fragment_retries.to_string());
 
-            let metadata = get_metadata_video(url, tx).await.unwrap();
-            let _ = tx.send(format!("Video metadata: {:?}", metadata));
+    args
+}
 
-            if let Err(e) = move_audio_file(&download_path, Path::new(dest_dir), &file_name, &metadata, tx).await {
-                let _ = tx.send(format!("Error al mover el archivo: {}", e));
-                return Err(e.to_string());
-            }
-            else {
-                let _ = tx.send("Archivo movido exitosamente".to_string());
-            }
+// yt-dlp no tiene una opción para fijar un campo: se copia el id a `genre` y después se reemplaza entero.
+// El reemplazo pasa por re.sub, así que las barras invertidas van dobles
+fn genre_args(genre: Option<&str>) -> Vec<String> {
+    let Some(genre) = genre else {
+        return Vec::new();
+    };
 
-            Ok(())
-        }
-        Err(e) => {
-            let _ = tx.send(format!("Error en la descarga: {}", e));
-            Err(e.to_string())
+    vec![
+        "--parse-metadata".to_string(),
+        "%(id)s:(?P<genre>.+)".to_string(),
+        "--replace-in-metadata".to_string(),
+        "genre".to_string(),
+        "(?s)^.+$".to_string(),
+        genre.replace('\\', "\\\\"),
+    ]
+}
+
+// Comentario con el enlace de origen. yt-dlp ya pone webpage_url en comment/purl, así que sin url_comment hay que vaciarlos
+fn comment_args(config: &Config) -> Vec<String> {
+    let comment = match (config.url_comment, config.url_comment_upload_date) {
+        (false, _) => {
+            return vec![
+                "--parse-metadata".to_string(),
+                ":(?P<meta_comment>)".to_string(),
+                "--parse-metadata".to_string(),
+                ":(?P<meta_purl>)".to_string(),
+            ]
         }
+        (true, false) => "%(webpage_url)s",
+        (true, true) => "%(webpage_url)s (%(upload_date>%Y-%m-%d|sin fecha)s)",
+    };
+
+    vec!["--parse-metadata".to_string(), format!("{}:%(meta_comment)s", comment)]
+}
+
+// En los temas de álbum yt-dlp toma título, artista, álbum y número de pista de los campos de YouTube Music.
+// Con un género o url_comment se incrusta también en cualquier otra descarga
+fn metadata_args(album: bool, genre: Option<&str>, config: &Config) -> Vec<String> {
+    if !album && genre.is_none() && !config.url_comment {
+        return Vec::new();
     }
+
+    let mut args = vec!["--embed-metadata".to_string()];
+    args.extend(genre_args(genre));
+    args.extend(comment_args(config));
+    args
 }
 
-fn run_ui(download_tx: tokio_mpsc::Sender<String>, status_rx: Receiver<String>) -> io::Result<()> {
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+// Incrusta la miniatura como portada cuadrada usando el ffmpeg que usa yt-dlp
+fn thumbnail_args(config: &Config) -> Vec<String> {
+    if !config.embed_thumbnail {
+        return Vec::new();
+    }
 
-    let mut input = String::new();
-    let mut messages: Vec<String> = Vec::new();
-    let mut button_focused = false;
+    let (extension, codec) = match config.thumbnail_format {
+        ThumbnailFormat::Jpg => ("jpg", "mjpeg"),
+        ThumbnailFormat::Png => ("png", "png"),
+    };
 
-    loop {
-        // Leer estados desde el worker sin bloquear (try_recv)
-        while let Ok(st) = status_rx.try_recv() {
-            messages.push(st);
-            if messages.len() > 300 {
-                messages.drain(0..(messages.len() - 300));
-            }
-        }
+    // Las comillas simples protegen las comas dentro del filtro de ffmpeg
+    let filter = match config.thumbnail_fit {
+        ThumbnailFit::Crop => "crop='min(iw,ih)':'min(iw,ih)'",
+        ThumbnailFit::Pad => "pad='max(iw,ih)':'max(iw,ih)':'(ow-iw)/2':'(oh-ih)/2'",
+    };
 
-        // Dibujar UI
-        terminal.draw(|f| {
-            let size = f.size();
+    vec![
+        "--embed-thumbnail".to_string(),
+        "--convert-thumbnails".to_string(),
+        extension.to_string(),
+        "--postprocessor-args".to_string(),
+        format!("ThumbnailsConvertor+FFmpeg_o:-c:v {} -vf \"{}\"", codec, filter),
+    ]
+}
 
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints(
-                    [
-                        Constraint::Min(3),
-                        Constraint::Length(3),
-                        Constraint::Length(3),
-                    ]
-                        .as_ref(),
-                )
-                .split(size);
+// Miniatura como archivo aparte, en su propia carpeta temporal para no confundirla con el audio
+fn thumbnail_file_args(config: &Config, output_path: &str) -> Vec<String> {
+    if !config.save_thumbnail {
+        return Vec::new();
+    }
 
-            let text: Vec<Spans> = messages
-                .iter()
-                .rev()
-                .map(|m| Spans::from(Span::raw(m.clone())))
-                .collect();
+    let mut args = vec![
+        "--write-thumbnail".to_string(),
+        "-o".to_string(),
+        format!("thumbnail:{}/thumbnail/%(title)s.%(ext)s", output_path),
+    ];
 
-            let messages_block = Paragraph::new(text)
-                .style(
-                    Style::default()
-                    .bg(Color::Rgb(66, 74, 118))
-                    .fg(Color::Rgb(167, 187, 236))
-                )
-                .block(
-                    Block::default()
-                    .borders(Borders::ALL)
-                    .title("Mensajes (recientes)")
-                );
-            f.render_widget(messages_block, chunks[0]);
+    // Con la portada incrustada ya se convierte al formato configurado; si no, webp → jpg
+    if !config.embed_thumbnail {
+        args.push("--convert-thumbnails".to_string());
+        args.push("jpg".to_string());
+    }
+
+    args
+}
+
+fn subtitle_args(config: &Config, output_path: &str) -> Vec<String> {
+    if config.subtitle_langs.is_empty() {
+        return Vec::new();
+    }
+
+    let mut args = vec!["--write-subs".to_string()];
+    // Con los dos pedidos yt-dlp usa los manuales y recurre a los automáticos solo si faltan
+    if config.auto_subtitles {
+        args.push("--write-auto-subs".to_string());
+    }
+    args.extend([
+        "--sub-langs".to_string(),
+        config.subtitle_langs.join(","),
+        "--convert-subs".to_string(),
+        "srt".to_string(),
+        "-o".to_string(),
+        format!("subtitle:{}/subs/%(title)s.%(ext)s", output_path),
+    ]);
+    args
+}
+
+// Reduce la miniatura al ancho pedido (nunca la agranda)
+async fn resize_thumbnail(source: &Path, max_width: u32) -> Result<(), String> {
+    let ffmpeg_path = ffmpeg_binary().map_err(|e| e.to_string())?;
+
+    let extension = source.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+    let target = source.with_extension(format!("resized.{}", extension));
+
+    let output = Command::new(ffmpeg_path)
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(source)
+        .arg("-vf")
+        .arg(format!("scale='min(iw,{})':-2", max_width))
+        .arg(&target)
+        .output()
+        .await
+        .map_err(|e| format!("No se pudo ejecutar ffmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg falló al redimensionar la miniatura: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    fs::rename(&target, source).await.map_err(|e| e.to_string())
+}
+
+// Frecuencia/canales y los argumentos del usuario van juntos en un solo --postprocessor-args
+fn postprocessor_args(config: &Config) -> Vec<String> {
+    let mut args = config.audio_ffmpeg_args();
+    if let Some(extra) = &config.postprocessor_args {
+        args.push(extra.trim().to_string());
+    }
+
+    if args.is_empty() {
+        return Vec::new();
+    }
+
+    vec![
+        "--postprocessor-args".to_string(),
+        format!("ExtractAudio+ffmpeg_o:{}", args.join(" ")),
+    ]
+}
+
+fn is_geo_restricted(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    stderr.contains("not available in your country")
+        || stderr.contains("geo restriction")
+        || stderr.contains("geo-restricted")
+}
+
+// yt-dlp no pudo usar ffmpeg para extraer o unir el audio
+fn is_ffmpeg_missing(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    stderr.contains("ffmpeg not found") || stderr.contains("ffmpeg is not installed")
+}
+
+// Omitidos por bitrate bajo en esta sesión, (enlace, destino); Ctrl+B los descarga igual
+static LOW_BITRATE_SKIPPED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+// Lo mismo que mostraría `yt-dlp -F`, pero desde la metadata ya descargada
+fn check_format_id(info: &VideoInfo, format_id: &str) -> Result<(), DownloadError> {
+    let audio_ids: Vec<&str> = info
+        .formats
+        .iter()
+        .filter(|format| format.has_audio())
+        .map(|format| format.format_id.as_str())
+        .collect();
+
+    match info.formats.iter().find(|format| format.format_id == format_id) {
+        Some(format) if format.has_audio() => Ok(()),
+        Some(_) => Err(DownloadError::UnknownFormat(format!(
+            "El formato {} no tiene audio (con audio: {})",
+            format_id,
+            audio_ids.join(", ")
+        ))),
+        None => Err(DownloadError::UnknownFormat(format!(
+            "El formato {} no existe para este video (con audio: {})",
+            format_id,
+            audio_ids.join(", ")
+        ))),
+    }
+}
+
+fn unavailable_reason(stderr: &str) -> Option<&'static str> {
+    let stderr = stderr.to_lowercase();
+
+    // "removed by the uploader" llega junto con "Video unavailable", por eso va antes
+    if stderr.contains("private video") {
+        Some("el video es privado")
+    } else if stderr.contains("removed by the uploader") {
+        Some("el video fue eliminado por quien lo subió")
+    } else if stderr.contains("members-only") || stderr.contains("members only") {
+        Some("el video es solo para miembros del canal")
+    } else if stderr.contains("video unavailable") {
+        Some("el video no está disponible")
+    } else {
+        None
+    }
+}
+
+// Lo propio de una descarga (video no disponible, ffmpeg, región) antes de la clasificación general
+fn yt_dlp_failure(code: Option<i32>, stderr: &str, ffmpeg_path: &Path, config: &Config) -> DownloadError {
+    if let Some(reason) = unavailable_reason(stderr) {
+        return DownloadError::Unavailable(reason);
+    }
+    if is_ffmpeg_missing(stderr) {
+        return DownloadError::FfmpegUnusable(ffmpeg_path.to_path_buf());
+    }
+    if is_geo_restricted(stderr) && !config.geo_bypass {
+        let line = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+        return DownloadError::GeoRestricted(line.to_string());
+    }
+    DownloadError::from_yt_dlp(code, stderr)
+}
+
+fn yt_dlp_error(code: Option<i32>, stderr: &str, config: &Config) -> String {
+    let mut message = format!("Error: yt-dlp terminó con un código no exitoso {:?}", code);
+
+    if let Some(line) = stderr.lines().rev().find(|l| !l.trim().is_empty()) {
+        message.push_str(&format!(": {}", line.trim()));
+    }
+
+    if is_geo_restricted(stderr) && !config.geo_bypass {
+        message.push_str(" (el video parece bloqueado en tu región; prueba activar `geo_bypass` en config.toml)");
+    }
+
+    message
+}
+
+async fn download_audio(
+    url: &str,
+    output_path: &str,
+    keep_source: bool,
+    album: bool,
+    genre: Option<&str>,
+    config: &Config,
+    tx: &StatusSender,
+) -> Result<PathBuf, DownloadError> {
+    let yt_dlp_path = yt_dlp_binary(tx)?;
+    // Siempre el de `libs`: sin esto yt-dlp busca un ffmpeg del sistema, que puede no existir
+    let ffmpeg_path = ffmpeg_binary()?;
+
+    let output_template = format!("{}/%(title)s.%(ext)s", output_path);
+
+    // Si la descarga se cancela se corta el proceso en vez de dejarlo corriendo
+    let mut command = Command::new(yt_dlp_path);
+    command
+        .kill_on_drop(true)
+        .arg("--ffmpeg-location")
+        .arg(&ffmpeg_path)
+        .arg("--extract-audio");
+    if let Some(temp) = download_temp_path(config, output_path) {
+        command.arg("--paths").arg(format!("temp:{}", temp.display()));
+    }
+    if config.reencodes() {
+        command
+            .arg("--audio-format")
+            .arg(config.effective_audio_format())
+            .arg("--audio-quality")
+            .arg(&config.audio_quality);
+    }
+    // Se extrae el códec tal cual y después solo se cambia el contenedor
+    if let Some(container) = &config.remux_container {
+        command.arg("--remux-video").arg(container);
+    }
+
+    if let Some(selector) = config.audio_format_selector() {
+        command.arg("-f").arg(selector);
+    }
+
+    // Se conserva el original para convertirlo a los formatos extra sin volver a descargar
+    if keep_source {
+        command.arg("--keep-video");
+    }
+
+    command.args(metadata_args(album, genre, config));
+
+    if config.sponsorblock {
+        command.arg("--sponsorblock-remove").arg("music_offtopic");
+    }
+
+    if config.split_chapters {
+        command
+            .arg("--split-chapters")
+            .arg("-o")
+            .arg(format!("chapter:{}/{}/%(section_number)03d - %(section_title)s.%(ext)s", output_path, CHAPTER_DIR));
+    }
+
+    // Sin --no-mtime el archivo toma el Last-Modified del servidor y la limpieza de `output` lo ve viejo
+    command
+        .arg("--no-playlist")
+        .arg("--no-mtime")
+        .args(network_args(config))
+        .args(thumbnail_args(config))
+        .args(thumbnail_file_args(config, output_path))
+        .args(subtitle_args(config, output_path))
+        .args(postprocessor_args(config))
+        .arg("--newline")
+        .arg("--progress-template")
+        .arg(format!("download:{}%(progress._percent_str)s", PROGRESS_PREFIX))
+        .arg("-o")
+        .arg(&output_template)
+        // Al final para que puedan pisar lo anterior
+        .args(config.extra_yt_dlp_args())
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!(?command, "ejecutando yt-dlp");
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| DownloadError::BinaryMissing(format!("No se pudo ejecutar yt-dlp: {}", e)))?;
+
+    // El avance se lee mientras corre; stderr se junta aparte para no trabar el proceso
+    let stdout = child.stdout.take();
+    let progress = async {
+        let Some(stdout) = stdout else {
+            return;
+        };
+        let mut lines = BufReader::new(stdout).lines();
+        let mut last = None;
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Some(pct) = parse_progress(&line) else {
+                continue;
+            };
+            // Un aviso por punto porcentual alcanza
+            if last != Some(pct.floor()) {
+                last = Some(pct.floor());
+                tx.stage(ItemState::Downloading { pct: Some(pct) });
+            }
+        }
+    };
+    let (output, ()) = tokio::join!(child.wait_with_output(), progress);
+    let output = output.map_err(|e| DownloadError::from_io(e, "Error al esperar a yt-dlp".to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        debug!(%stderr, "yt-dlp terminó con error");
+        return Err(yt_dlp_failure(output.status.code(), &stderr, &ffmpeg_path, config));
+    }
+
+    let _ = tx.send(format!("Audio descargado correctamente en: {}", output_path));
+
+    Ok(PathBuf::from(output_path))
+}
+
+// Subcarpeta de la descarga donde yt-dlp deja los capítulos (--split-chapters)
+const CHAPTER_DIR: &str = "chapters";
+
+// "003 - Intro.mp3" -> (3, "Intro")
+fn parse_chapter_file(file_name: &str) -> Option<(u32, String)> {
+    let stem = file_name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(file_name);
+    let (number, title) = stem.split_once(" - ")?;
+    Some((number.trim().parse().ok()?, title.trim().to_string()))
+}
+
+const PROGRESS_PREFIX: &str = "gif-tube-progress ";
+
+// Línea de --progress-template, ej. "gif-tube-progress  42.3%"
+fn parse_progress(line: &str) -> Option<f32> {
+    line.strip_prefix(PROGRESS_PREFIX)?
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+}
+
+async fn download_video(
+    url: &str,
+    output_path: &str,
+    config: &Config,
+    tx: &StatusSender,
+) -> Result<PathBuf, DownloadError> {
+    let yt_dlp_path = yt_dlp_binary(tx)?;
+    let ffmpeg_path = ffmpeg_binary()?;
+
+    let output_template = format!("{}/%(title)s.%(ext)s", output_path);
+    let temp_args: Vec<String> = download_temp_path(config, output_path)
+        .map(|temp| vec!["--paths".to_string(), format!("temp:{}", temp.display())])
+        .unwrap_or_default();
+
+    let child = Command::new(yt_dlp_path)
+        .kill_on_drop(true)
+        .arg("--ffmpeg-location")
+        .arg(&ffmpeg_path)
+        .args(temp_args)
+        .arg("-f")
+        .arg("bv*+ba/b")
+        .arg("--merge-output-format")
+        .arg("mp4")
+        .arg("--no-playlist")
+        .arg("--no-mtime")
+        .args(network_args(config))
+        .arg("-o")
+        .arg(&output_template)
+        .arg(url)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| DownloadError::BinaryMissing(format!("No se pudo ejecutar yt-dlp: {}", e)))?;
+
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| DownloadError::from_io(e, "Error al esperar a yt-dlp".to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(yt_dlp_failure(output.status.code(), &stderr, &ffmpeg_path, config));
+    }
+
+    let _ = tx.send(format!("Video descargado correctamente en: {}", output_path));
+
+    Ok(PathBuf::from(output_path))
+}
+
+// Inicial del artista en mayúscula, sin tilde; números y símbolos van a "#" y otros alfabetos a "Otros"
+fn shard_name(artist: &str) -> String {
+    let Some(first) = artist.chars().find(|c| !c.is_whitespace()) else {
+        return "#".to_string();
+    };
+
+    let folded = match first.to_lowercase().next().unwrap_or(first) {
+        'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+        'é' | 'è' | 'ê' | 'ë' => 'e',
+        'í' | 'ì' | 'î' | 'ï' => 'i',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+        'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'ñ' => 'n',
+        'ç' => 'c',
+        other => other,
+    };
+
+    if folded.is_ascii_alphabetic() {
+        folded.to_ascii_uppercase().to_string()
+    } else if folded.is_alphabetic() {
+        "Otros".to_string()
+    } else {
+        "#".to_string()
+    }
+}
+
+// Carpeta del tema según el esquema; los de un álbum van en "Artista/Álbum"
+fn artist_dir(dest_dir: &Path, metadata: &VideoMetadata, layout: LibraryLayout) -> PathBuf {
+    let artist = sanitize_filename_or(metadata.author_name.as_str(), UNKNOWN_ARTIST);
+
+    let dest_dir = match layout.shards {
+        ShardScheme::None => dest_dir.to_path_buf(),
+        ShardScheme::FirstLetter => dest_dir.join(shard_name(&artist)),
+    };
+
+    if let Some(album) = &metadata.album {
+        return dest_dir.join(artist).join(sanitize_filename(album));
+    }
+
+    match layout.naming {
+        NamingScheme::Legacy | NamingScheme::ArtistFolder => dest_dir.join(artist),
+        NamingScheme::ArtistTitle | NamingScheme::TitleOnly => dest_dir,
+    }
+}
+
+// Si el título ya nombra al artista al principio ("Artista - Tema"), sin distinguir mayúsculas
+fn title_starts_with_artist(metadata: &VideoMetadata) -> bool {
+    let artist = metadata.author_name.trim().to_lowercase();
+    !artist.is_empty() && metadata.title.trim_start().to_lowercase().starts_with(&artist)
+}
+
+// Nombre del archivo sin extensión según el esquema. En un álbum: "NN - Título"
+fn base_file_name(metadata: &VideoMetadata, layout: LibraryLayout) -> String {
+    let title = sanitize_filename(metadata.title.as_str());
+
+    if metadata.album.is_some() {
+        return match metadata.track_number {
+            Some(number) => format!("{:02} - {}", number, title),
+            None => title,
+        };
+    }
+
+    let artist = sanitize_filename_or(metadata.author_name.as_str(), UNKNOWN_ARTIST);
+
+    let legacy_prefix = match layout.artist_prefix {
+        ArtistPrefix::Always => true,
+        ArtistPrefix::Never => false,
+        ArtistPrefix::Auto => !title_starts_with_artist(metadata),
+    };
+
+    match layout.naming {
+        NamingScheme::Legacy if !legacy_prefix => title,
+        NamingScheme::Legacy | NamingScheme::ArtistTitle => format!("{}-{}", artist, title),
+        NamingScheme::TitleOnly | NamingScheme::ArtistFolder => title,
+    }
+}
+
+fn file_extension(file_name: &str) -> &str {
+    file_name.rsplit('.').next().unwrap_or("mp3")
+}
+
+// Ruta que debería tener un tema dentro de la biblioteca (sin sufijos por colisión)
+fn expected_path(dest_dir: &Path, metadata: &VideoMetadata, layout: LibraryLayout, extension: &str) -> PathBuf {
+    artist_dir(dest_dir, metadata, layout).join(format!("{}.{}", base_file_name(metadata, layout), extension))
+}
+
+// Arma la ruta final dentro de `dest_dir` (carpeta del artista, nombre sin choques) y crea la carpeta
+async fn target_path(
+    dest_dir: &Path,
+    file_name: &str,
+    metadata: &VideoMetadata,
+    layout: LibraryLayout,
+    duration: Option<u64>,
+    tx: &StatusSender,
+) -> Result<PathBuf, String> {
+
+    let root = dest_dir;
+    let dest_dir = artist_dir(root, metadata, layout);
+    if layout.safe_paths && path_escapes(root, &dest_dir) {
+        return Err(format!("safe_paths: {:?} queda fuera del destino {:?}, no se escribe", dest_dir, root));
+    }
+
+    if !dest_dir.exists() {
+        let _ = tx.send(format!("La ruta {:?} no existe; créala o revisa el path", &dest_dir));
+        match fs::create_dir_all(&dest_dir).await {
+            Ok(_) => {
+                let _ = tx.send(format!("Directorio creado exitosamente: {:?}", &dest_dir));
+            },
+            Err(e) => {
+                return Err(format!("Error al crear el directorio de destino: {:?}", e));
+            }
+        }
+    }
+
+    let stem = target_stem(file_name, metadata, layout, duration);
+    let (final_path, suffix) = free_path(&dest_dir, &stem, file_extension(file_name));
+    if layout.safe_paths {
+        ensure_inside(root, &final_path)?;
+    }
+    if suffix > 0 {
+        let _ = tx.send(format!(
+            "El archivo '{}' ya existe en el destino. Moviendo con un nuevo nombre...",
+            file_name
+        ));
+    }
+
+    Ok(final_path)
+}
+
+// Sin tocar el disco: `path` sale de `root` o lo reemplaza (un componente absoluto en `join` pisa todo lo anterior)
+fn path_escapes(root: &Path, path: &Path) -> bool {
+    match path.strip_prefix(root) {
+        Ok(relative) => relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)),
+        Err(_) => true,
+    }
+}
+
+// Con la carpeta ya creada: resuelve enlaces y ".." de verdad y compara contra el destino resuelto
+fn ensure_inside(root: &Path, path: &Path) -> Result<(), String> {
+    let escapes = || format!("safe_paths: {:?} queda fuera del destino {:?}, no se escribe", path, root);
+    let (Some(parent), Some(_)) = (path.parent(), path.file_name()) else {
+        return Err(escapes());
+    };
+    let root = std::fs::canonicalize(root).map_err(|e| format!("No se pudo resolver el destino {:?}: {}", root, e))?;
+    let parent = std::fs::canonicalize(parent).map_err(|e| format!("No se pudo resolver {:?}: {}", parent, e))?;
+    if parent.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(escapes())
+    }
+}
+
+// Nombre sin extensión que toma el archivo en el destino
+fn target_stem(file_name: &str, metadata: &VideoMetadata, layout: LibraryLayout, duration: Option<u64>) -> String {
+    let mut stem = if layout.keep_original_name {
+        let original = file_name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(file_name);
+        sanitize_filename(original)
+    } else {
+        base_file_name(metadata, layout)
+    };
+    if let Some(secs) = duration {
+        stem.push_str(&duration_suffix(secs));
+    }
+    stem
+}
+
+// Primera ruta libre: "stem.ext" o "stem_N.ext"; devuelve también N (0 si no hubo choque)
+fn free_path(dest_dir: &Path, stem: &str, extension: &str) -> (PathBuf, u32) {
+    let mut path = dest_dir.join(format!("{}.{}", stem, extension));
+    let mut suffix = 0;
+    while path.exists() {
+        suffix += 1;
+        path = dest_dir.join(format!("{}_{}.{}", stem, suffix, extension));
+    }
+    (path, suffix)
+}
+
+// Con collision_confirm_after: si el nombre ya tiene demasiadas copias se pregunta qué hacer.
+// Sobrescribir borra el archivo sin sufijo para que el nuevo tome su lugar
+async fn confirm_collision(
+    dest_dir: &Path,
+    file_name: &str,
+    metadata: &VideoMetadata,
+    layout: LibraryLayout,
+    duration: Option<u64>,
+    limit: u32,
+    tx: &StatusSender,
+) -> Result<(), DownloadError> {
+    let dir = artist_dir(dest_dir, metadata, layout);
+    let stem = target_stem(file_name, metadata, layout, duration);
+    let extension = file_extension(file_name);
+    let (_, suffix) = free_path(&dir, &stem, extension);
+    if suffix <= limit {
+        return Ok(());
+    }
+
+    let existing = dir.join(format!("{}.{}", stem, extension));
+    match tx.ask_collision(existing.clone(), suffix).await {
+        CollisionChoice::Overwrite => {
+            fs::remove_file(&existing)
+                .await
+                .map_err(|e| DownloadError::from_io(e, format!("No se pudo sobrescribir {:?}", existing)))?;
+            let _ = tx.send(format!("Se sobrescribe {:?}", existing));
+            Ok(())
+        }
+        CollisionChoice::Skip => Err(DownloadError::Duplicate(existing)),
+        CollisionChoice::Rename => Ok(()),
+    }
+}
+
+// `dest_dirs`: el destino principal primero y después las copias adicionales.
+// Con varios destinos se copia a cada uno y el original se borra solo si todas las copias salieron;
+// un destino que falla no frena a los demás, pero la descarga no se da por terminada (DownloadError::Incomplete)
+async fn move_audio_file(
+    src_dir: &Path,
+    dest_dirs: &[PathBuf],
+    file_name: &str,
+    metadata: &VideoMetadata,
+    layout: LibraryLayout,
+    duration: Option<u64>,
+    tx: &StatusSender,
+) -> Result<PathBuf, DownloadError> {
+    let source_path = src_dir.join(file_name);
+
+    let Some((primary, mirrors)) = dest_dirs.split_first() else {
+        return Err(DownloadError::Move("No hay ninguna carpeta de destino".to_string()));
+    };
+
+    if mirrors.is_empty() {
+        let final_path = target_path(primary, file_name, metadata, layout, duration, tx)
+            .await
+            .map_err(DownloadError::Move)?;
+
+        debug!(source = ?source_path, target = ?final_path, "moviendo archivo");
+        move_file(&source_path, &final_path)
+            .await
+            .map_err(|e| DownloadError::from_io(e, format!("No se pudo mover {:?} a {:?}", source_path, final_path)))?;
+
+        let _ = tx.send(format!("Archivo movido a: {:?}", final_path.parent().unwrap_or(primary)));
+        return Ok(final_path);
+    }
+
+    let mut primary_path = None;
+    let mut missing = Vec::new();
+    for (index, dest_dir) in dest_dirs.iter().enumerate() {
+        let result = match target_path(dest_dir, file_name, metadata, layout, duration, tx).await {
+            Ok(final_path) => {
+                debug!(source = ?source_path, target = ?final_path, "copiando archivo");
+                copy_file(&source_path, &final_path)
+                    .await
+                    .map(|_| final_path.clone())
+                    .map_err(|e| DownloadError::from_io(e, format!("No se pudo copiar {:?} a {:?}", source_path, final_path)))
+            }
+            Err(e) => Err(DownloadError::Move(e)),
+        };
+
+        match result {
+            Ok(final_path) => {
+                let _ = tx.send(format!("Copiado a: {:?}", final_path));
+                if index == 0 {
+                    primary_path = Some(final_path);
+                }
+            }
+            Err(e) => {
+                warn!(dest_dir = ?dest_dir, error = %e, "destino fallido");
+                let _ = tx.error(format!("Error en el destino {:?}: {}", dest_dir, e));
+                missing.push(dest_dir.clone());
+            }
+        }
+    }
+
+    match primary_path {
+        Some(path) if missing.is_empty() => {
+            let _ = fs::remove_file(&source_path).await;
+            Ok(path)
+        }
+        primary => Err(DownloadError::Incomplete { primary, missing }),
+    }
+}
+
+// La imagen toma el mismo nombre que el audio ya movido, así queda al lado y con el mismo sufijo
+async fn move_thumbnail_file(
+    src_dir: &Path,
+    file_name: &str,
+    audio_path: &Path,
+    max_width: Option<u32>,
+    tx: &StatusSender,
+) -> Result<PathBuf, String> {
+    let source_path = src_dir.join(file_name);
+
+    if let Some(max_width) = max_width {
+        if let Err(e) = resize_thumbnail(&source_path, max_width).await {
+            let _ = tx.send(format!("No se pudo redimensionar la miniatura: {}", e));
+        }
+    }
+
+    let final_path = audio_path.with_extension(file_extension(file_name));
+
+    debug!(source = ?source_path, target = ?final_path, "moviendo miniatura");
+    move_file(&source_path, &final_path)
+        .await
+        .map_err(|e| format!("Error al mover la miniatura: {}", e))?;
+
+    Ok(final_path)
+}
+
+// yt-dlp deja "<título>.<idioma>.srt"; quedan como "<audio>.<idioma>.srt"
+async fn move_subtitle_files(src_dir: &str, audio_path: &Path) -> Result<Vec<PathBuf>, String> {
+    // Sin subtítulos en esos idiomas yt-dlp ni crea la carpeta
+    if !Path::new(src_dir).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut moved = Vec::new();
+    for file_name in get_downloaded_file_names(src_dir).await? {
+        let Some(stem) = file_name.strip_suffix(".srt") else {
+            continue;
+        };
+        let lang = stem.rsplit_once('.').map(|(_, lang)| lang).unwrap_or("und");
+        let final_path = audio_path.with_extension(format!("{}.srt", lang));
+
+        debug!(source = file_name, target = ?final_path, "moviendo subtítulos");
+        move_file(&Path::new(src_dir).join(&file_name), &final_path)
+            .await
+            .map_err(|e| format!("Error al mover los subtítulos: {}", e))?;
+        moved.push(final_path);
+    }
+    Ok(moved)
+}
+
+#[derive(Debug)]
+struct DownloadOutcome {
+    path: PathBuf,
+    title: String,
+    artist: String,
+    bytes: u64,
+    album: Option<String>,
+    track_number: Option<u32>,
+    video_path: Option<PathBuf>,
+    thumbnail_path: Option<PathBuf>,
+    subtitle_paths: Vec<PathBuf>,
+    extra_paths: Vec<PathBuf>,
+    // Un tema por capítulo (split_chapters); vacío si el video no tiene capítulos
+    chapter_paths: Vec<PathBuf>,
+    fingerprint: Option<Vec<u32>>,
+}
+
+// `album`: el enlace viene de un álbum de YouTube Music y se ordena por álbum en vez de por canal
+// Carpeta temporal compartida; cada descarga usa una subcarpeta propia
+const STAGING_DIR: &str = "output";
+
+static NEXT_JOB: AtomicUsize = AtomicUsize::new(0);
+
+// El pid evita chocar con carpetas que quedaron de una sesión anterior
+fn job_dir(url: &str) -> String {
+    let video_id = parse_youtube_url(url)
+        .ok()
+        .and_then(|parsed| parsed.video_id)
+        .unwrap_or_else(|| "descarga".to_string());
+
+    format!(
+        "{}/{}-{}-{}",
+        STAGING_DIR,
+        video_id,
+        std::process::id(),
+        NEXT_JOB.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+// Parciales de yt-dlp de la carpeta de trabajo `output_dir` (o su subcarpeta) dentro de download_temp_dir
+fn download_temp_path(config: &Config, output_dir: &str) -> Option<PathBuf> {
+    let temp_dir = config.download_temp_dir.as_ref()?;
+    let relative = Path::new(output_dir).strip_prefix(STAGING_DIR).unwrap_or(Path::new(output_dir));
+    Some(Path::new(temp_dir).join(relative))
+}
+
+// Carpeta temporal de una descarga; se borra al soltarla, también si la descarga se cancela a mitad
+struct StagingDir {
+    path: String,
+    keep: bool,
+    // En download_temp_dir solo quedan parciales: se borra siempre
+    temp: Option<PathBuf>,
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        if let Some(temp) = &self.temp {
+            if let Err(e) = std::fs::remove_dir_all(temp) {
+                debug!(temp_dir = ?temp, error = %e, "no se pudo borrar la carpeta de parciales");
+            }
+        }
+        if self.keep {
+            return;
+        }
+        // Lo que quede (originales, parciales) se descarta con la carpeta
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            debug!(work_dir = self.path, error = %e, "no se pudo borrar la carpeta temporal");
+        }
+    }
+}
+
+async fn download(
+    url: &str,
+    dest_dir: &str,
+    album: bool,
+    config: &Config,
+    tx: &StatusSender,
+) -> Result<DownloadOutcome, DownloadError> {
+    let path = job_dir(url);
+    let temp = download_temp_path(config, &path);
+    let mut staging = StagingDir { path, keep: false, temp };
+    fs::create_dir_all(&staging.path)
+        .await
+        .map_err(|e| DownloadError::from_io(e, "Error al crear el directorio de salida".to_string()))?;
+    if let Some(temp) = &staging.temp {
+        fs::create_dir_all(temp)
+            .await
+            .map_err(|e| DownloadError::from_io(e, format!("Error al crear la carpeta de parciales {:?}", temp)))?;
+    }
+
+    let result = download_staged(url, dest_dir, album, &staging.path, config, tx).await;
+
+    // Si solo falló el movimiento, la carpeta se conserva para reintentarlo
+    staging.keep = matches!(&result, Err(DownloadError::MoveFailed(..)));
+    result
+}
+
+// Descarga terminada que no se pudo mover al destino (disco lleno, pendrive quitado, permisos).
+// Los archivos quedan en su carpeta de `output` para reintentar solo el movimiento
+#[derive(Debug, Clone)]
+struct PendingMove {
+    url: String,
+    work_dir: String,
+    // El principal primero, como en move_audio_file
+    dest_dirs: Vec<PathBuf>,
+    // Destinos a los que todavía les falta el audio principal
+    missing_dirs: Vec<PathBuf>,
+    // Dónde quedó el audio principal si el destino principal ya lo tiene
+    moved: Option<PathBuf>,
+    // El audio principal primero, después los formatos extra
+    files: Vec<PathBuf>,
+    metadata: VideoMetadata,
+    layout: LibraryLayout,
+    duration: Option<u64>,
+    fingerprint: Option<Vec<u32>>,
+}
+
+// Con channel_allowlist: un canal que no está en la lista se confirma antes de descargar.
+// Sin la metadata completa se usa el autor de oembed
+async fn check_channel(url: &str, info: Option<&VideoInfo>, config: &Config, tx: &StatusSender) -> Result<(), DownloadError> {
+    let (author, names) = match info {
+        Some(info) => (info.author().to_string(), info.channel_names()),
+        None => {
+            let metadata = get_metadata_video(url, config, tx)
+                .await
+                .map_err(|e| DownloadError::Metadata(format!("no se pudo saber el canal: {}", e)))?;
+            (metadata.author_name.clone(), vec![metadata.author_name])
+        }
+    };
+    if config.allows_channel(&names) {
+        return Ok(());
+    }
+
+    if tx.ask_channel(author.clone(), url.to_string()).await {
+        let _ = tx.send(format!("Se descarga igual el canal {}", author));
+        Ok(())
+    } else {
+        Err(DownloadError::ChannelNotAllowed(author))
+    }
+}
+
+// Huella del audio descargado; avisa si se parece a algo que ya está en el historial.
+// Sin fpcalc la descarga sigue igual, solo sin huella
+async fn check_duplicate(
+    path: &Path,
+    policy: DuplicatePolicy,
+    tx: &StatusSender,
+) -> Result<Option<Vec<u32>>, DownloadError> {
+    let fingerprint = match fingerprint::compute(path).await {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            let _ = tx.send(format!("Sin huella de audio: {}", e));
+            return Ok(None);
+        }
+    };
+
+    let entries = match history::load(Path::new(HISTORY_FILE)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(error = %e, "no se pudo leer el historial para buscar duplicados");
+            Vec::new()
+        }
+    };
+
+    if let Some((entry, score)) = fingerprint::find_duplicate(&fingerprint, &entries) {
+        warn!(existing = ?entry.path, score, "posible duplicado");
+        let _ = tx.send(format!(
+            "Posible duplicado ({:.0}%) de {} - {}: {:?}",
+            score * 100.0,
+            entry.artist,
+            entry.title,
+            entry.path
+        ));
+        if policy == DuplicatePolicy::Skip {
+            return Err(DownloadError::Duplicate(entry.path.clone()));
+        }
+    }
+
+    Ok(Some(fingerprint))
+}
+
+// Movimientos que agotaron los reintentos; Ctrl+R los vuelve a intentar
+static PENDING_MOVES: Mutex<Vec<PendingMove>> = Mutex::new(Vec::new());
+
+async fn finish_pending_move(pending: &mut PendingMove, tx: &StatusSender) -> Result<PathBuf, String> {
+    let Some((main, extras)) = pending.files.split_first() else {
+        return Err("No quedó ningún archivo para mover".to_string());
+    };
+
+    // El audio principal va solo a los destinos que todavía no lo tienen
+    if !pending.missing_dirs.is_empty() {
+        let (Some(dir), Some(name)) = (main.parent(), main.file_name()) else {
+            return Err(format!("Ruta inválida: {:?}", main));
+        };
+        let result = move_audio_file(
+            dir,
+            &pending.missing_dirs,
+            &name.to_string_lossy(),
+            &pending.metadata,
+            pending.layout,
+            pending.duration,
+            tx,
+        )
+        .await;
+
+        match result {
+            Ok(path) => {
+                pending.moved.get_or_insert(path);
+                pending.missing_dirs.clear();
+            }
+            Err(e) => {
+                // Lo que sí llegó no se vuelve a copiar en el próximo intento
+                if let DownloadError::Incomplete { primary, missing } = &e {
+                    if pending.moved.is_none() {
+                        pending.moved = primary.clone();
+                    }
+                    pending.missing_dirs = missing.clone();
+                }
+                return Err(e.to_string());
+            }
+        }
+    }
+    let path = pending.moved.clone().ok_or("No quedó ningún archivo para mover")?;
+
+    for file in extras {
+        let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+            continue;
+        };
+        let result = move_audio_file(
+            dir,
+            &pending.dest_dirs,
+            &name.to_string_lossy(),
+            &pending.metadata,
+            pending.layout,
+            pending.duration,
+            tx,
+        )
+        .await;
+        if let Err(e) = result {
+            let _ = tx.error(format!("Error al mover {:?}: {}", name, e));
+        }
+    }
+
+    if let Err(e) = fs::remove_dir_all(&pending.work_dir).await {
+        debug!(work_dir = pending.work_dir, error = %e, "no se pudo borrar la carpeta temporal");
+    }
+
+    let bytes = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+    let entry = HistoryEntry {
+        url: pending.url.clone(),
+        path: path.clone(),
+        dest_root: pending.dest_dirs.first().cloned().unwrap_or_default(),
+        artist: pending.metadata.author_name.clone(),
+        title: pending.metadata.title.clone(),
+        bytes,
+        album: pending.metadata.album.clone(),
+        track_number: pending.metadata.track_number,
+        error: None,
+        fingerprint: pending.fingerprint.clone(),
+        downloaded_at: history::now_secs(),
+    };
+    if let Err(e) = history::record(Path::new(HISTORY_FILE), entry) {
+        warn!(error = %e, "no se pudo actualizar el historial");
+    }
+    if let Err(e) = stats::record(Path::new(STATS_FILE), history::now_secs(), bytes) {
+        warn!(error = %e, "no se pudieron actualizar las estadísticas");
+    }
+
+    let _ = tx.send(format!(
+        "Done: {} - {} -> {:?} ({})",
+        pending.metadata.author_name, pending.metadata.title, path, format_bytes(bytes)
+    ));
+    let _ = tx.downloaded(path.clone());
+    Ok(path)
+}
+
+// Reintenta solo el movimiento; si sigue fallando el archivo queda pendiente
+async fn retry_move(mut pending: PendingMove, retries: u32, tx: &StatusSender) -> bool {
+    for attempt in 1..=retries {
+        tokio::time::sleep(RETRY_DELAY * attempt).await;
+        let _ = tx.send(format!("Reintentando mover ({}/{}): {}", attempt, retries, pending.metadata.title));
+        match finish_pending_move(&mut pending, tx).await {
+            Ok(_) => return true,
+            Err(e) => {
+                warn!(url = pending.url, attempt, error = e, "reintento de movimiento fallido");
+                let _ = tx.send(format!("Intento {}/{} de mover fallido: {}", attempt, retries, e));
+            }
+        }
+    }
+
+    let _ = tx.send(format!(
+        "El archivo quedó en {}; Ctrl+R reintenta moverlo sin volver a descargar",
+        pending.work_dir
+    ));
+    PENDING_MOVES.lock().unwrap().push(pending);
+    false
+}
+
+// Cuánto tardó cada fase de una descarga (--timings); una fase puede sumar varios tramos
+#[derive(Default)]
+struct PhaseTimings(Vec<(&'static str, Duration)>);
+
+impl PhaseTimings {
+    fn add(&mut self, phase: &'static str, since: Instant) {
+        let elapsed = since.elapsed();
+        match self.0.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.0.push((phase, elapsed)),
+        }
+    }
+}
+
+// "metadata 0.4s, descarga 12.1s, proceso 0.0s, movimiento 0.3s"
+impl fmt::Display for PhaseTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases: Vec<String> = self
+            .0
+            .iter()
+            .map(|(name, elapsed)| format!("{} {:.1}s", name, elapsed.as_secs_f64()))
+            .collect();
+        write!(f, "{}", phases.join(", "))
+    }
+}
+
+async fn download_staged(
+    url: &str,
+    dest_dir: &str,
+    album: bool,
+    output_dir: &str,
+    config: &Config,
+    tx: &StatusSender,
+) -> Result<DownloadOutcome, DownloadError> {
+    // El video usa su propia carpeta temporal para no mezclarse con el audio
+    let video_output_dir = &format!("{}/video", output_dir);
+    let convert_dir = &format!("{}/convert", output_dir);
+    let thumbnail_dir = &format!("{}/thumbnail", output_dir);
+    let subtitle_dir = &format!("{}/subs", output_dir);
+    // Antes de gastar la descarga: el principal tiene que aceptar archivos; una copia que no puede solo avisa
+    for (index, dest) in config.dest_dirs(dest_dir).into_iter().enumerate() {
+        if let Err(error) = dest_writable(&dest).await {
+            let error = DownloadError::NotWritable { dest_dir: dest, error };
+            if index == 0 {
+                return Err(error);
+            }
+            let _ = tx.error(format!("{}; esa copia va a fallar", error));
+        }
+    }
+
+    // Sin recodificar no hay un original aparte: los formatos extra se convierten desde el propio audio
+    let keep_source = !config.extra_audio_formats.is_empty() && config.reencodes();
+
+    let mut timings = PhaseTimings::default();
+    let started = Instant::now();
+    // La metadata completa hace falta para los álbumes, el sidecar, la duración en el nombre y el bitrate mínimo
+    let info = if album
+        || config.sidecar_format.is_some()
+        || config.duration_in_filename
+        || config.min_audio_bitrate.is_some()
+        || config.audio_format_id.is_some()
+        || config.detect_speech
+        || config.audio_language.is_some()
+        || !config.channel_allowlist.is_empty()
+    {
+        match get_metadata_json(url, config, tx).await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                let _ = tx.send(format!("Sin metadata completa: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    if !config.channel_allowlist.is_empty() {
+        check_channel(url, info.as_ref(), config, tx).await?;
+    }
+    timings.add("metadata", started);
+
+    // Si el tema no trae datos de álbum se sigue con la lógica por canal
+    let album_metadata = if album {
+        info.as_ref().and_then(VideoInfo::album_metadata)
+    } else {
+        None
+    };
+
+    let speech_config;
+    let config = match &info {
+        Some(info) if config.detect_speech && info.looks_like_speech(config.speech_min_minutes) => {
+            let _ = tx.send(format!("Parece una charla o podcast: se guarda como {}", config.speech_audio_format));
+            let mut speech = config.clone();
+            speech.apply_kind(ContentKind::Speech);
+            speech_config = speech;
+            &speech_config
+        }
+        _ => config,
+    };
+    let audio_format = config.effective_audio_format();
+    let audio_quality = config.audio_quality.as_str();
+
+    if let (Some(language), Some(info), None) = (&config.audio_language, &info, &config.audio_format_id) {
+        let languages = info.audio_languages();
+        if languages.len() > 1 && !languages.iter().any(|(known, _)| known.starts_with(language.as_str())) {
+            let _ = tx.send(format!(
+                "No hay audio en {} (hay {}); se usa la pista original",
+                language,
+                info.audio_languages_label()
+            ));
+        }
+    }
+
+    if let Some(format_id) = &config.audio_format_id {
+        let info = info
+            .as_ref()
+            .ok_or_else(|| DownloadError::Metadata(format!("no se pudo comprobar si existe el formato {}", format_id)))?;
+        check_format_id(info, format_id)?;
+        let _ = tx.send(format!("Formato elegido: {}", format_id));
+    }
+
+    if let Some(min) = config.min_audio_bitrate {
+        match info.as_ref().and_then(VideoInfo::best_audio_bitrate) {
+            Some(kbps) if kbps < min as f64 => return Err(DownloadError::LowBitrate { kbps, min }),
+            Some(kbps) => {
+                let _ = tx.send(format!("Mejor audio disponible: {:.0} kbps", kbps));
+            }
+            None => {
+                let _ = tx.send("yt-dlp no informa el bitrate; se descarga sin comprobar el mínimo".to_string());
+            }
+        }
+    }
+
+    let genre = config.genre_for(Path::new(dest_dir));
+    if let Some(genre) = genre {
+        let _ = tx.send(format!("Género: {}", genre));
+    }
+
+    tx.stage(ItemState::Downloading { pct: None });
+    let started = Instant::now();
+    let download_path = download_audio(
+        url,
+        output_dir,
+        keep_source,
+        album_metadata.is_some(),
+        genre,
+        config,
+        tx,
+    )
+    .await?;
+
+    let (file_name, source_name) = if keep_source {
+        get_primary_and_source_names(output_dir, audio_format)
+            .await
+            .map_err(DownloadError::MissingFile)?
+    } else {
+        let file_name = get_downloaded_file_name(output_dir, downloaded_extension(config))
+            .await
+            .map_err(DownloadError::MissingFile)?
+            .ok_or_else(|| DownloadError::MissingFile(format!("No se encontró el archivo descargado en {}", output_dir)))?;
+        (file_name, None)
+    };
+    let _ = tx.send(format!("File name: {}", file_name));
+    timings.add("descarga", started);
+
+    let started = Instant::now();
+    let mut metadata = match album_metadata {
+        Some(metadata) => metadata,
+        None => match get_metadata_video(url, config, tx).await {
+            Ok(metadata) => metadata,
+            // El audio ya está descargado: sin oembed (TLS, red bloqueada) se sigue con otro nombre
+            Err(e) => {
+                let _ = tx.error(format!("Metadata fetch failed (TLS/network): {}", e));
+                let metadata = fallback_metadata(info.as_ref(), &file_name);
+                let _ = tx.send(format!("Se guarda como {} - {}", metadata.author_name, metadata.title));
+                metadata
+            }
+        },
+    };
+    timings.add("metadata", started);
+    metadata.title = config.clean_title(&metadata.title);
+    let _ = tx.send(format!("Video metadata: {:?}", metadata));
+
+    let duration = info.as_ref().and_then(VideoInfo::duration_secs);
+    if let Some(secs) = duration {
+        let _ = tx.send(format!("Duración: {}", format_duration(secs)));
+    }
+    let name_duration = duration.filter(|_| config.duration_in_filename);
+
+    let started = Instant::now();
+    let fingerprint = if config.detect_duplicates {
+        check_duplicate(&download_path.join(&file_name), config.duplicate_policy, tx).await?
+    } else {
+        None
+    };
+
+    // Las conversiones se hacen antes de mover, porque el original puede ser el propio archivo principal
+    let mut converted = Vec::new();
+    if keep_source {
+        fs::create_dir_all(convert_dir)
+            .await
+            .map_err(|e| DownloadError::from_io(e, "Error al crear el directorio de conversión".to_string()))?;
+
+        let source = download_path.join(source_name.as_deref().unwrap_or(&file_name));
+        let ffmpeg_args = config.audio_ffmpeg_args();
+        for extra_format in &config.extra_audio_formats {
+            match convert_audio(&source, convert_dir, extra_format, audio_quality, &ffmpeg_args, tx).await {
+                Ok(target) => converted.push(target),
+                Err(e) => {
+                    let _ = tx.error(format!("Error en la conversión a {}: {}", extra_format, e));
+                }
+            }
+        }
+    }
+
+    timings.add("proceso", started);
+
+    let dest_dirs = config.dest_dirs(dest_dir);
+    if let Some(limit) = config.collision_confirm_after {
+        confirm_collision(&dest_dirs[0], &file_name, &metadata, config.library_layout(), name_duration, limit, tx).await?;
+    }
+    tx.stage(ItemState::Moving);
+    let started = Instant::now();
+    let moved = move_audio_file(&download_path, &dest_dirs, &file_name, &metadata, config.library_layout(), name_duration, tx).await;
+    let path = match moved {
+        Ok(path) => path,
+        Err(e) => {
+            let mut files = vec![download_path.join(&file_name)];
+            files.extend(converted);
+            let (moved, missing_dirs) = match &e {
+                DownloadError::Incomplete { primary, missing } => (primary.clone(), missing.clone()),
+                _ => (None, dest_dirs.clone()),
+            };
+            let pending = PendingMove {
+                url: url.to_string(),
+                work_dir: output_dir.to_string(),
+                dest_dirs,
+                missing_dirs,
+                moved,
+                files,
+                metadata,
+                layout: config.library_layout(),
+                duration: name_duration,
+                fingerprint,
+            };
+            return Err(DownloadError::MoveFailed(Box::new(pending), Box::new(e)));
+        }
+    };
+    let _ = tx.send("Audio movido exitosamente".to_string());
+
+    let mut extra_paths = Vec::new();
+    for target in converted {
+        let target_name = target.file_name().unwrap().to_string_lossy().into_owned();
+        match move_audio_file(
+            Path::new(convert_dir),
+            &dest_dirs,
+            &target_name,
+            &metadata,
+            config.library_layout(),
+            name_duration,
+            tx,
+        )
+        .await
+        {
+            Ok(extra_path) => extra_paths.push(extra_path),
+            Err(e) => {
+                let _ = tx.error(format!("Error al mover {}: {}", target_name, e));
+            }
+        }
+    }
+
+    let mut chapter_paths = Vec::new();
+    if config.split_chapters {
+        let chapter_dir = download_path.join(CHAPTER_DIR);
+        let mut chapters: Vec<(String, u32, String)> = get_downloaded_file_names(&chapter_dir.to_string_lossy())
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|name| {
+                let (number, title) = parse_chapter_file(&name)?;
+                Some((name, number, title))
+            })
+            .collect();
+        chapters.sort_by_key(|(_, number, _)| *number);
+        if chapters.is_empty() {
+            let _ = tx.send("El video no tiene capítulos; queda un solo archivo".to_string());
+        }
+
+        // Los capítulos se ordenan como un álbum con el título del video
+        for (name, number, title) in chapters {
+            let chapter = VideoMetadata {
+                title,
+                author_name: metadata.author_name.clone(),
+                album: Some(metadata.title.clone()),
+                track_number: Some(number),
+            };
+            match move_audio_file(&chapter_dir, &dest_dirs, &name, &chapter, config.library_layout(), None, tx).await {
+                Ok(chapter_path) => chapter_paths.push(chapter_path),
+                Err(e) => {
+                    let _ = tx.error(format!("Error al mover el capítulo {}: {}", name, e));
+                }
+            }
+        }
+    }
+
+    if let Some(source_name) = source_name {
+        let _ = fs::remove_file(download_path.join(source_name)).await;
+    }
+    timings.add("movimiento", started);
+    if config.timings {
+        info!(url, timings = %timings, "tiempos de la descarga");
+        let _ = tx.send(format!("Tiempos: {}", timings));
+    }
+
+    // Si falta la miniatura no se pierde la descarga, solo se avisa
+    let mut thumbnail_path = None;
+    if config.save_thumbnail {
+        match get_downloaded_file_name(thumbnail_dir, None).await {
+            Ok(Some(thumbnail_name)) => {
+                match move_thumbnail_file(Path::new(thumbnail_dir), &thumbnail_name, &path, config.thumbnail_max_width, tx).await {
+                    Ok(moved) => thumbnail_path = Some(moved),
+                    Err(e) => {
+                        let _ = tx.send(e);
+                    }
+                }
+            }
+            _ => {
+                let _ = tx.send("yt-dlp no dejó ninguna miniatura para guardar".to_string());
+            }
+        }
+    }
+
+    let mut subtitle_paths = Vec::new();
+    if !config.subtitle_langs.is_empty() {
+        match move_subtitle_files(subtitle_dir, &path).await {
+            Ok(moved) if moved.is_empty() => {
+                let _ = tx.send(format!("Sin subtítulos en: {}", config.subtitle_langs.join(", ")));
+            }
+            Ok(moved) => subtitle_paths = moved,
+            Err(e) => {
+                let _ = tx.send(e);
+            }
+        }
+    }
+
+    if let Some(format) = config.sidecar_format {
+        match info.as_ref().map(|info| info.sidecar_data(url, &metadata)) {
+            Some(data) if data.is_complete() => match sidecar::write(&path, format, &data) {
+                Ok(sidecar_path) => {
+                    let _ = tx.send(format!("Sidecar: {:?}", sidecar_path));
+                }
+                Err(e) => {
+                    let _ = tx.send(e);
+                }
+            },
+            _ => {
+                let _ = tx.send("Metadata incompleta, no se escribe el sidecar".to_string());
+            }
+        }
+    }
+
+    // El audio ya está en el destino: si falla el video solo se avisa, así no se reintenta todo ni se duplica el audio
+    let video_path = if config.keep_video {
+        match download_video_copy(url, video_output_dir, dest_dir, &metadata, name_duration, config, tx).await {
+            Ok(path) => Some(path),
+            Err(e) => {
+                let _ = tx.error(format!("No se guardó el video (el audio sí): {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let bytes = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+
+    Ok(DownloadOutcome {
+        path,
+        title: metadata.title,
+        artist: metadata.author_name,
+        bytes,
+        album: metadata.album,
+        track_number: metadata.track_number,
+        video_path,
+        thumbnail_path,
+        subtitle_paths,
+        extra_paths,
+        chapter_paths,
+        fingerprint,
+    })
+}
+
+async fn download_video_copy(
+    url: &str,
+    video_output_dir: &str,
+    dest_dir: &str,
+    metadata: &VideoMetadata,
+    duration: Option<u64>,
+    config: &Config,
+    tx: &StatusSender,
+) -> Result<PathBuf, String> {
+    fs::create_dir_all(video_output_dir)
+        .await
+        .map_err(|e| format!("Error al crear el directorio temporal de video: {}", e))?;
+
+    let download_path = download_video(url, video_output_dir, config, tx)
+        .await
+        .map_err(|e| format!("Error en la descarga del video: {}", e))?;
+    let file_name = get_downloaded_file_name(video_output_dir, None)
+        .await?
+        .ok_or_else(|| format!("No se encontró el video descargado en {}", video_output_dir))?;
+    let videos_dir = Path::new(dest_dir).join("videos");
+
+    let path = move_audio_file(&download_path, &[videos_dir], &file_name, metadata, config.library_layout(), duration, tx)
+        .await
+        .map_err(|e| format!("Error al mover el video: {}", e))?;
+    let _ = tx.send("Video movido exitosamente".to_string());
+    Ok(path)
+}
+
+// Salida de `--print "%(playlist_count|)s\t%(url)s"`: las urls y el total, si yt-dlp lo conoce
+fn parse_playlist_page(stdout: &str) -> (Vec<String>, Option<usize>) {
+    let mut total = None;
+    let entries = stdout
+        .lines()
+        .filter_map(|line| {
+            let (count, url) = line.split_once('\t').unwrap_or(("", line));
+            total = total.or_else(|| count.trim().parse().ok());
+            Some(url.trim().to_string()).filter(|url| !url.is_empty())
+        })
+        .collect();
+    (entries, total)
+}
+
+// Entradas `start` a `start + count - 1` (desde 1); una página más corta que `count` es la última
+async fn expand_playlist_page(
+    url: &str,
+    start: usize,
+    count: usize,
+    config: &Config,
+    tx: &StatusSender,
+) -> Result<(Vec<String>, Option<usize>), String> {
+    let _busy = tx.busy("Leyendo la playlist");
+
+    let yt_dlp_path = yt_dlp_binary(tx).map_err(|e| e.to_string())?;
+
+    let output = Command::new(yt_dlp_path)
+        .arg("--flat-playlist")
+        .arg("--playlist-items")
+        .arg(format!("{}:{}", start, start + count - 1))
+        .arg("--print")
+        .arg("%(playlist_count|)s\t%(url)s")
+        .args(network_args(config))
+        .arg(url)
+        .output()
+        .await
+        .map_err(|e| format!("No se pudo ejecutar yt-dlp: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(yt_dlp_error(output.status.code(), &stderr, config));
+    }
+
+    Ok(parse_playlist_page(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// Elige entre el tema suelto o la playlist para un enlace ya validado
+fn resolve_url(parsed: &YoutubeUrl, prefer_playlist: bool) -> Option<String> {
+    match (&parsed.video_id, &parsed.playlist_id) {
+        (Some(_), Some(_)) if prefer_playlist => parsed.playlist_url(),
+        (Some(_), _) => parsed.single_url(),
+        _ => parsed.playlist_url(),
+    }
+}
+
+// Espera antes de reintentar; crece con cada intento
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+async fn download_and_report(url: &str, dest_dir: &str, album: bool, config: &Config, tx: &StatusSender) -> bool {
+    let _ = tx.send(format!("Descargando: {}", url));
+    info!(url, dest_dir, album, "descarga iniciada");
+
+    let attempts = config.download_retries + 1;
+    let mut attempt = 1;
+    let result = loop {
+        match download(url, dest_dir, album, config, tx).await {
+            Err(e) if attempt < attempts && e.is_retryable() => {
+                warn!(url, attempt, error = %e, "reintentando descarga");
+                let _ = tx.send(format!("Intento {}/{} fallido: {}. Reintentando...", attempt, attempts, e));
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            result => break result,
+        }
+    };
+
+    match result {
+        Ok(outcome) => {
+            info!(url, path = ?outcome.path, bytes = outcome.bytes, "descarga completa");
+            let _ = tx.send(format!(
+                "Done: {} - {} -> {:?} ({})",
+                outcome.artist, outcome.title, outcome.path, format_bytes(outcome.bytes)
+            ));
+
+            let entry = HistoryEntry {
+                url: url.to_string(),
+                path: outcome.path.clone(),
+                dest_root: PathBuf::from(dest_dir),
+                artist: outcome.artist.clone(),
+                title: outcome.title.clone(),
+                bytes: outcome.bytes,
+                album: outcome.album.clone(),
+                track_number: outcome.track_number,
+                error: None,
+                fingerprint: outcome.fingerprint,
+                downloaded_at: history::now_secs(),
+            };
+            if let Err(e) = history::record(Path::new(HISTORY_FILE), entry) {
+                warn!(error = %e, "no se pudo actualizar el historial");
+                let _ = tx.send(format!("No se pudo actualizar el historial: {}", e));
+            }
+            if let Err(e) = stats::record(Path::new(STATS_FILE), history::now_secs(), outcome.bytes) {
+                warn!(error = %e, "no se pudieron actualizar las estadísticas");
+            }
+
+            if let Some(video_path) = &outcome.video_path {
+                let _ = tx.send(format!("Video: {:?}", video_path));
+            }
+            if let Some(thumbnail_path) = &outcome.thumbnail_path {
+                let _ = tx.send(format!("Miniatura: {:?}", thumbnail_path));
+            }
+            for subtitle_path in &outcome.subtitle_paths {
+                let _ = tx.send(format!("Subtítulos: {:?}", subtitle_path));
+            }
+            for extra_path in &outcome.extra_paths {
+                let _ = tx.send(format!("Formato extra: {:?}", extra_path));
+            }
+            for (index, chapter_path) in outcome.chapter_paths.iter().enumerate() {
+                let _ = tx.send(format!("Capítulo {}/{}: {:?}", index + 1, outcome.chapter_paths.len(), chapter_path));
+            }
+            let _ = tx.downloaded(outcome.path);
+            true
+        }
+        Err(e) => {
+            // Descartado a propósito, no cuenta como fallo
+            match &e {
+                DownloadError::Duplicate(_) => {
+                    let _ = tx.send(format!("Omitido: {} -> {}", url, e));
+                    return true;
+                }
+                DownloadError::ChannelNotAllowed(_) => {
+                    let _ = tx.send(format!("Omitido: {} -> {} (--any-channel para descargarlo igual)", url, e));
+                    return true;
+                }
+                _ => {}
+            }
+
+            error!(url, error = %e, "descarga fallida");
+
+            // El archivo ya está descargado: no se vuelve a bajar, solo se reintenta moverlo
+            if let DownloadError::MoveFailed(pending, _) = &e {
+                let _ = tx.error(format!("Error: {} -> {}", url, e));
+                return retry_move((**pending).clone(), config.move_retries, tx).await;
+            }
+
+            if let DownloadError::LowBitrate { .. } = e {
+                let _ = tx.error(format!(
+                    "Omitido: {} -> {} (Ctrl+B o --any-bitrate para descargarlo igual)",
+                    url, e
+                ));
+                LOW_BITRATE_SKIPPED.lock().unwrap().push((url.to_string(), dest_dir.to_string()));
+                return false;
+            }
+
+            let DownloadError::Unavailable(unavailable) = e else {
+                let _ = tx.error(format!("Error: {} -> {}", url, e));
+                return false;
+            };
+
+            let _ = tx.error(format!("No disponible: {} -> {} (no se reintenta)", url, unavailable));
+            let entry = HistoryEntry {
+                url: url.to_string(),
+                path: PathBuf::new(),
+                dest_root: PathBuf::from(dest_dir),
+                artist: String::new(),
+                title: String::new(),
+                bytes: 0,
+                album: None,
+                track_number: None,
+                error: Some(unavailable.to_string()),
+                fingerprint: None,
+                downloaded_at: history::now_secs(),
+            };
+            if let Err(e) = history::record(Path::new(HISTORY_FILE), entry) {
+                warn!(error = %e, "no se pudo actualizar el historial");
+            }
+            false
+        }
+    }
+}
+
+// Descarga un enlace, o cada entrada si es una playlist, e informa el resultado
+async fn process_url(url: &str, dest_dir: &str, config: &Config, tx: &StatusSender) -> bool {
+    let (is_playlist, is_album) = parse_youtube_url(url)
+        .map(|parsed| (parsed.is_playlist_only(), parsed.is_album()))
+        .unwrap_or((false, false));
+
+    if !is_playlist {
+        return download_and_report(url, dest_dir, false, config, tx).await;
+    }
+
+    // De a una página: cada tema se descarga y se mueve antes de pedir más, así `output` nunca junta la playlist entera
+    let kind = if is_album { "Álbum" } else { "Playlist" };
+    let page_size = config.playlist_page_size;
+    let mut progress = BatchProgress {
+        key: url.to_string(),
+        total: 0,
+        done: 0,
+        failed: 0,
+        skipped: 0,
+    };
+
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut stopped = false;
+    loop {
+        let start = progress.done + 1;
+        let (entries, total) = match expand_playlist_page(url, start, page_size, config, tx).await {
+            Ok(page) => page,
+            Err(e) => {
+                let _ = tx.error(format!("Error: {} -> {}", url, e));
+                if start == 1 {
+                    return false;
+                }
+                stopped = true;
+                break;
+            }
+        };
+        let last_page = entries.len() < page_size;
+
+        // Sin total de yt-dlp se cuenta lo leído hasta ahora
+        progress.total = total.unwrap_or(0).max(progress.done + entries.len());
+        if start == 1 {
+            match total {
+                Some(total) => {
+                    let _ = tx.send(format!("{} con {} entradas: {}", kind, total, url));
+                }
+                None => {
+                    let _ = tx.send(format!("{}: {} (se lee de a {} entradas)", kind, url, page_size));
+                }
+            }
+        }
+        let _ = tx.batch(progress.clone());
+
+        for entry in &entries {
+            let (downloads, bytes) = stats::session();
+            if let Some(limit) = config.session_limit_reached(downloads, bytes) {
+                let _ = tx.send(format!("Session limit reached ({}); se detiene la playlist", limit));
+                stopped = true;
+                break;
+            }
+            let _ = tx.send(format!("[{}/{}] {}", progress.done + 1, progress.total, entry));
+            let ok = download_and_report(entry, dest_dir, is_album, config, tx).await;
+
+            progress.done += 1;
+            if ok {
+                succeeded += 1;
+            } else {
+                failed += 1;
+                progress.failed += 1;
+            }
+            let _ = tx.batch(progress.clone());
+
+            if !ok && config.playlist_error_policy == PlaylistErrorPolicy::FailFast {
+                let _ = tx.send("fail-fast: se detiene la playlist en el primer error".to_string());
+                stopped = true;
+                break;
+            }
+        }
+
+        if stopped || last_page {
+            break;
+        }
+    }
+
+    let skipped = progress.total - progress.done;
+    if skipped > 0 {
+        progress.skipped = skipped;
+        let _ = tx.batch(progress);
+    }
+    let mut summary = format!("{} terminada: {} correctas, {} fallidas", kind, succeeded, failed);
+    if skipped > 0 {
+        summary.push_str(&format!(", {} sin descargar", skipped));
+    }
+    info!(url, succeeded, failed, skipped, "playlist terminada");
+    let _ = tx.send(summary);
+
+    failed == 0 && !stopped
+}
+
+// Toma enlaces de la cola y descarga hasta `max_concurrent_downloads` a la vez
+async fn run_worker(
+    queue: Arc<DownloadQueue>,
+    settings: Arc<RwLock<Config>>,
+    tx: StatusSender,
+    active_downloads: Arc<AtomicUsize>,
+) -> RunSummary {
+    run_worker_with(queue, settings, tx, active_downloads, |url, dest_dir, config, tx| async move {
+        process_url(&url, &dest_dir, &config, &tx).await
+    })
+    .await
+}
+
+fn panic_message(error: JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+    let payload = error.into_panic();
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "pánico sin mensaje".to_string())
+}
+
+// `download` recibe (url, destino, ajustes, estado); se separa para poder probar el worker sin yt-dlp
+async fn run_worker_with<F, Fut>(
+    queue: Arc<DownloadQueue>,
+    settings: Arc<RwLock<Config>>,
+    tx: StatusSender,
+    active_downloads: Arc<AtomicUsize>,
+    download: F,
+) -> RunSummary
+where
+    F: Fn(String, String, Config, StatusSender) -> Fut,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    let mut limit = settings.read().unwrap().max_concurrent_downloads;
+    let slots = Arc::new(Semaphore::new(limit));
+    let mut tasks = JoinSet::new();
+    let mut summary = RunSummary::default();
+    // Descargas en curso por sitio (ver host_key)
+    let per_host: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+
+    loop {
+        // El límite puede cambiar desde los ajustes; bajarlo solo quita lugares libres
+        let wanted = settings.read().unwrap().max_concurrent_downloads;
+        if wanted > limit {
+            slots.add_permits(wanted - limit);
+            limit = wanted;
+        } else if wanted < limit {
+            limit -= slots.forget_permits(limit - wanted);
+        }
+
+        // El semáforo nunca se cierra
+        let slot = slots.clone().acquire_owned().await.unwrap();
+        enforce_session_limit(&settings.read().unwrap(), &queue, &tx);
+        // Un sitio que llegó a su límite no frena a los demás: se toma el siguiente de otro sitio
+        let host_limit = settings.read().unwrap().max_concurrent_per_host;
+        let ready = |item: &QueueItem| {
+            let active = per_host.lock().unwrap();
+            active.get(&host_key(&item.url)).copied().unwrap_or(0) < host_limit
+        };
+        let Some(item) = queue.next(ready).await else {
+            break;
+        };
+        let host = host_key(&item.url);
+        *per_host.lock().unwrap().entry(host.clone()).or_default() += 1;
+
+        // Cada descarga usa los ajustes vigentes al empezar
+        let mut config = settings.read().unwrap().clone();
+        if item.any_bitrate {
+            config.min_audio_bitrate = None;
+        }
+        if let Some(kind) = item.kind {
+            config.apply_kind(kind);
+        }
+        let dest_dir = item.dest.clone().or_else(|| config.dest_dir.clone()).unwrap_or_default();
+        let queue = queue.clone();
+        let tx = tx.for_item(item.id);
+        let active_downloads = active_downloads.clone();
+        let per_host = per_host.clone();
+        let settings = settings.clone();
+        let cancel = queue.cancel_signal(item.id);
+        tx.stage(ItemState::FetchingMetadata);
+        // En su propia tarea: si entra en pánico se informa como error y la cola sigue
+        let mut job = tokio::spawn(download(item.url.clone(), dest_dir, config, tx.clone()));
+        tasks.spawn(async move {
+            active_downloads.fetch_add(1, Ordering::SeqCst);
+            // Al cancelar se suelta la descarga: se corta yt-dlp y se borra su carpeta temporal
+            let ok = tokio::select! {
+                result = &mut job => match result {
+                    Ok(ok) => {
+                        tx.stage(if ok { ItemState::Done } else { ItemState::Error });
+                        ok
+                    }
+                    Err(e) => {
+                        tx.stage(ItemState::Error);
+                        let message = panic_message(e);
+                        error!(url = item.url, error = message, "la descarga terminó con un pánico");
+                        let _ = tx.error(format!("Error interno: {} -> {}", item.url, message));
+                        false
+                    }
+                },
+                _ = cancel.notified() => {
+                    job.abort();
+                    tx.stage(ItemState::Cancelled);
+                    let _ = tx.send(format!("{}: {}", DownloadError::Cancelled, item.url));
+                    false
+                }
+            };
+            active_downloads.fetch_sub(1, Ordering::SeqCst);
+            if let Some(active) = per_host.lock().unwrap().get_mut(&host) {
+                *active -= 1;
+            }
+            if ok {
+                enforce_session_limit(&settings.read().unwrap(), &queue, &tx);
+            }
+            queue.wake();
+            if let Some(progress) = queue.complete(item.id, ok) {
+                if progress.is_finished() {
+                    let _ = tx.send(format!(
+                        "Lote terminado: {} correctos, {} fallidos",
+                        progress.total - progress.failed,
+                        progress.failed
+                    ));
+                }
+                let _ = tx.batch(progress);
+            }
+            drop(slot);
+            ok
+        });
+
+        while let Some(result) = tasks.try_join_next() {
+            summary.record(result.unwrap_or(false));
+        }
+    }
+
+    // Las descargas en curso terminan aunque la cola se haya cerrado
+    while let Some(result) = tasks.join_next().await {
+        summary.record(result.unwrap_or(false));
+    }
+    summary
+}
+
+// Con session_max_downloads / session_max_mib: al llegar al límite no se empiezan más descargas.
+// Con la interfaz la cola queda en pausa hasta reiniciar los contadores (Ctrl+N); sin interfaz se cierra
+fn enforce_session_limit(config: &Config, queue: &DownloadQueue, tx: &StatusSender) {
+    let (downloads, bytes) = stats::session();
+    let Some(limit) = config.session_limit_reached(downloads, bytes) else {
+        return;
+    };
+    if queue.is_paused() {
+        return;
+    }
+
+    queue.set_paused(true);
+    if queue.is_finished() {
+        queue.close();
+        let _ = tx.send(format!("Session limit reached ({}); no se empiezan más descargas", limit));
+    } else {
+        let _ = tx.send(format!("Session limit reached ({}); Ctrl+N reinicia los contadores y Ctrl+D reanuda", limit));
+    }
+}
+
+// Busca de antemano los títulos de la cola; si falla el título queda vacío y no se reintenta
+async fn run_metadata_prefetch(queue: Arc<DownloadQueue>, settings: Arc<RwLock<Config>>) {
+    let slots = Arc::new(Semaphore::new(settings.read().unwrap().max_concurrent_metadata));
+    let mut attempted: HashSet<u64> = HashSet::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+
+        let items = queue.snapshot();
+        attempted.retain(|id| items.iter().any(|item| item.id == *id));
+
+        for item in items {
+            if item.started || item.title.is_some() || !attempted.insert(item.id) {
+                continue;
+            }
+
+            // El semáforo nunca se cierra
+            let slot = slots.clone().acquire_owned().await.unwrap();
+            let queue = queue.clone();
+            let config = settings.read().unwrap().clone();
+            tokio::spawn(async move {
+                // Sin mensajes de estado: los títulos son solo informativos
+                let (tx, _rx) = status::channel();
+                match get_metadata_video(&item.url, &config, &tx).await {
+                    Ok(metadata) => queue.set_title(
+                        item.id,
+                        format!("{} - {}", metadata.author_name, config.clean_title(&metadata.title)),
+                    ),
+                    Err(e) => debug!(url = item.url, error = %e, "sin título anticipado"),
+                }
+                drop(slot);
+            });
+        }
+    }
+}
+
+// Sin TUI los mensajes se imprimen en texto plano
+fn spawn_plain_printer(status_rx: Receiver<StatusEvent>, quiet: bool) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for event in status_rx {
+            match event {
+                StatusEvent::Message(message) if !quiet => println!("{}", message),
+                StatusEvent::Error(message) => eprintln!("{}", message),
+                _ => {}
+            }
+        }
+    })
+}
+
+fn resolve_input(input: &str, prefer_playlist: bool, allow_other_sites: bool) -> Result<String, String> {
+    match parse_media_url(input, allow_other_sites)? {
+        MediaUrl::Youtube(parsed) => resolve_url(&parsed, prefer_playlist)
+            .ok_or_else(|| format!("Enlace sin video ni playlist: {}", input)),
+        MediaUrl::Other(url) => Ok(url),
+    }
+}
+
+fn read_clipboard_url(prefer_playlist: bool, allow_other_sites: bool) -> Result<String, String> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|e| format!("No se pudo leer el portapapeles: {}", e))?;
+
+    resolve_input(text.trim(), prefer_playlist, allow_other_sites)
+        .map_err(|e| format!("El portapapeles no tiene un enlace válido ({})", e))
+}
+
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| format!("No se pudo copiar al portapapeles: {}", e))
+}
+
+// Resultado de un modo sin interfaz, para elegir el código de salida
+#[derive(Debug, Default, Clone, Copy)]
+struct RunSummary {
+    succeeded: usize,
+    failed: usize,
+    // Enlaces rechazados antes de intentar la descarga
+    invalid: usize,
+}
+
+impl RunSummary {
+    fn record(&mut self, ok: bool) {
+        if ok {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    fn merge(&mut self, other: RunSummary) {
+        self.succeeded += other.succeeded;
+        self.failed += other.failed;
+        self.invalid += other.invalid;
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.failed == 0 && self.invalid == 0 {
+            0
+        } else if self.succeeded > 0 {
+            EXIT_PARTIAL_FAILURE
+        } else if self.failed == 0 {
+            EXIT_BAD_ARGUMENTS
+        } else {
+            EXIT_TOTAL_FAILURE
+        }
+    }
+}
+
+async fn run_headless(
+    urls: &[String],
+    dest_dir: &str,
+    config: &Config,
+    prefer_playlist: bool,
+    quiet: bool,
+) -> RunSummary {
+    let (status_tx, status_rx) = status::channel();
+    let printer = spawn_plain_printer(status_rx, quiet);
+
+    let mut summary = RunSummary::default();
+    for input in urls {
+        match resolve_input(input, prefer_playlist, config.allow_other_sites) {
+            Ok(url) => summary.record(process_url(&url, dest_dir, config, &status_tx).await),
+            Err(e) => {
+                let _ = status_tx.error(format!("Error: {}", e));
+                summary.invalid += 1;
+            }
+        }
+    }
+
+    drop(status_tx);
+    let _ = printer.join();
+    summary
+}
+
+// Encola los enlaces a medida que llegan por stdin; al EOF se termina lo pendiente y sale
+// Con `allow_duplicates` (--allow-duplicates) se encola aunque el mismo video ya esté en la cola
+async fn run_stdin(dest_dir: &str, config: &Config, prefer_playlist: bool, allow_duplicates: bool, quiet: bool) -> RunSummary {
+    let (status_tx, status_rx) = status::channel();
+    let printer = spawn_plain_printer(status_rx, quiet);
+
+    let queue = Arc::new(DownloadQueue::new(None, Vec::new()));
+
+    let mut worker_config = config.clone();
+    worker_config.dest_dir = Some(dest_dir.to_string());
+    let worker = tokio::spawn(run_worker(
+        queue.clone(),
+        Arc::new(RwLock::new(worker_config)),
+        status_tx.clone(),
+        Arc::new(AtomicUsize::new(0)),
+    ));
+
+    let mut summary = RunSummary::default();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let parsed = split_dest_override(line)
+            .and_then(|(input, dest)| Ok((resolve_input(input, prefer_playlist, config.allow_other_sites)?, dest)));
+        match parsed {
+            Ok((url, _)) if !allow_duplicates && queued_duplicate(&queue, &url).is_some() => {
+                let _ = status_tx.send(format!("Already in queue: {}", url));
+            }
+            Ok((url, dest)) => {
+                let id = queue.push(url.clone(), dest);
+                let ahead = queue.ahead_of(id).unwrap_or(0);
+                let _ = status_tx.send(format!("Queued ({} ahead): {}", ahead, url));
+            }
+            Err(e) => {
+                let _ = status_tx.error(format!("Error: {}", e));
+                summary.invalid += 1;
+            }
+        }
+    }
+
+    queue.finish();
+    summary.merge(worker.await.unwrap_or_default());
+
+    drop(status_tx);
+    let _ = printer.join();
+    summary
+}
+
+// Archivos que deja una extensión del navegador en la carpeta de --watch
+const INBOX_EXTENSIONS: [&str; 2] = ["url", "txt"];
+
+// Enlaces de un acceso directo .url ("URL=https://...") o de un .txt (uno o varios por línea)
+fn urls_in_inbox_file(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim())
+        .map(|line| line.strip_prefix("URL=").unwrap_or(line))
+        .flat_map(str::split_whitespace)
+        .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(str::to_string)
+        .collect()
+}
+
+// --watch: revisa `inbox` cada vez que notify avisa un cambio, hasta Ctrl+C. Un archivo se lee recién cuando su tamaño
+// no cambió desde la pasada anterior (puede estar a medio escribir); si está bloqueado se prueba en la siguiente
+async fn run_watch(
+    inbox: &Path,
+    dest_dir: &str,
+    config: &Config,
+    prefer_playlist: bool,
+    allow_duplicates: bool,
+    quiet: bool,
+) -> RunSummary {
+    let (status_tx, status_rx) = status::channel();
+    let printer = spawn_plain_printer(status_rx, quiet);
+
+    let queue = Arc::new(DownloadQueue::new(None, Vec::new()));
+
+    let mut worker_config = config.clone();
+    worker_config.dest_dir = Some(dest_dir.to_string());
+    let worker = tokio::spawn(run_worker(
+        queue.clone(),
+        Arc::new(RwLock::new(worker_config)),
+        status_tx.clone(),
+        Arc::new(AtomicUsize::new(0)),
+    ));
+
+    info!(inbox = ?inbox, "vigilando carpeta");
+    let _ = status_tx.send(format!("Vigilando {:?} (Ctrl+C para salir)", inbox));
+
+    let mut summary = RunSummary::default();
+    // Tamaño de cada archivo en la pasada anterior
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    // Ya encolados pero que no se pudieron borrar; no se vuelven a leer
+    let mut processed: HashSet<PathBuf> = HashSet::new();
+    // El callback de notify corre en otro hilo; solo avisa que hay que volver a mirar la carpeta
+    let (event_tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = event_tx.send(event.is_ok());
+    })
+    .and_then(|mut watcher| watcher.watch(inbox, RecursiveMode::NonRecursive).map(|_| watcher));
+    let watching = match &watcher {
+        Ok(_) => true,
+        Err(e) => {
+            warn!(inbox = ?inbox, error = %e, "sin avisos de cambios en la carpeta");
+            let _ = status_tx.error(format!(
+                "No se pueden recibir avisos de {:?} ({}); se revisa cada {} s",
+                inbox, e, config.watch_interval_secs
+            ));
+            false
+        }
+    };
+
+    let mut scanned = false;
+    let mut interval = tokio::time::interval(Duration::from_secs(config.watch_interval_secs));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                // Con avisos, el intervalo solo vuelve a mirar archivos a medio escribir o bloqueados
+                if watching && scanned && sizes.is_empty() {
+                    continue;
+                }
+            }
+            Some(_) = events.recv() => {
+                // Una copia genera varios avisos seguidos; alcanza con una pasada
+                while events.try_recv().is_ok() {}
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+        scanned = true;
+
+        let mut entries = match fs::read_dir(inbox).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(inbox = ?inbox, error = %e, "no se pudo leer la carpeta vigilada");
+                continue;
+            }
+        };
+        let mut seen = HashMap::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let is_inbox_file = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| INBOX_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if !is_inbox_file || !metadata.is_file() || processed.contains(&path) {
+                continue;
+            }
+
+            let size = metadata.len();
+            seen.insert(path.clone(), size);
+            if size == 0 || sizes.get(&path) != Some(&size) {
+                continue;
+            }
+            let text = match fs::read_to_string(&path).await {
+                Ok(text) => text,
+                Err(e) => {
+                    debug!(path = ?path, error = %e, "archivo todavía no legible");
+                    continue;
+                }
+            };
+
+            info!(path = ?path, "archivo nuevo en la carpeta vigilada");
+            for input in urls_in_inbox_file(&text) {
+                match resolve_input(&input, prefer_playlist, config.allow_other_sites) {
+                    Ok(url) if !allow_duplicates && queued_duplicate(&queue, &url).is_some() => {
+                        let _ = status_tx.send(format!("Already in queue: {}", url));
+                    }
+                    Ok(url) => {
+                        let id = queue.push(url.clone(), None);
+                        let ahead = queue.ahead_of(id).unwrap_or(0);
+                        let _ = status_tx.send(format!("Queued ({} ahead): {}", ahead, url));
+                    }
+                    Err(e) => {
+                        let _ = status_tx.error(format!("Error en {:?}: {}", path, e));
+                        summary.invalid += 1;
+                    }
+                }
+            }
+
+            seen.remove(&path);
+            if let Err(e) = fs::remove_file(&path).await {
+                warn!(path = ?path, error = %e, "no se pudo borrar el archivo ya leído");
+                processed.insert(path);
+            }
+        }
+        sizes = seen;
+    }
+
+    let _ = status_tx.send("Se dejan de vigilar archivos; terminando las descargas en curso".to_string());
+    queue.finish();
+    summary.merge(worker.await.unwrap_or_default());
+
+    drop(status_tx);
+    let _ = printer.join();
+    summary
+}
+
+// Copia a `<destino>.part` y renombra al terminar, para no dejar un archivo a medias con el nombre final
+async fn copy_file(source: &Path, target: &Path) -> io::Result<()> {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    if let Err(e) = fs::copy(source, &partial).await {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    if let Err(e) = verify_copy(source, &partial).await {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    if let Err(e) = fs::rename(&partial, target).await {
+        let _ = fs::remove_file(&partial).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Antes de dar la copia por buena (y de borrar el original): se vuelca al disco y se compara el tamaño.
+// Un pendrive puede aceptar la copia y quedarse con un archivo cortado
+async fn verify_copy(source: &Path, copy: &Path) -> io::Result<()> {
+    fs::OpenOptions::new().write(true).open(copy).await?.sync_all().await?;
+
+    let expected = fs::metadata(source).await?.len();
+    let copied = fs::metadata(copy).await?.len();
+    if copied != expected {
+        return Err(io::Error::other(format!(
+            "la copia quedó incompleta ({} de {} bytes); se conserva el original",
+            copied, expected
+        )));
+    }
+    Ok(())
+}
+
+// En el mismo disco es un rename atómico; entre discos (ej. a un pendrive) se copia y se borra el original
+async fn move_file(source: &Path, target: &Path) -> io::Result<()> {
+    if fs::rename(source, target).await.is_ok() {
+        return Ok(());
+    }
+
+    debug!(source = ?source, target = ?target, "rename no disponible, se copia");
+    copy_file(source, target).await?;
+    fs::remove_file(source).await
+}
+
+// Video corto y estable para probar la conexión con YouTube
+const SELF_CHECK_URL: &str = "https://www.youtube.com/watch?v=jNQXAC9IVRw";
+
+async fn binary_runs(path: Result<PathBuf, DownloadError>, version_flag: &str) -> Result<String, String> {
+    let output = Command::new(path.map_err(|e| e.to_string())?)
+        .arg(version_flag)
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or("").trim().to_string())
+}
+
+async fn dest_writable(dest_dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest_dir).await?;
+
+    let probe = dest_dir.join(".gif_tube_desk_check");
+    fs::write(&probe, b"ok").await?;
+    fs::remove_file(&probe).await
+}
+
+async fn all_dests_writable(dest_dirs: &[PathBuf]) -> Result<String, String> {
+    let mut checked = Vec::new();
+    for dest_dir in dest_dirs {
+        dest_writable(dest_dir)
+            .await
+            .map_err(|e| format!("{}: {}", dest_dir.display(), e))?;
+        checked.push(dest_dir.display().to_string());
+    }
+    Ok(checked.join(", "))
+}
+
+// Versiones de los binarios de `libs`, consultadas una vez al iniciar
+struct BinaryVersions {
+    yt_dlp: Result<String, String>,
+    ffmpeg: Result<String, String>,
+}
+
+impl BinaryVersions {
+    async fn detect(tx: &StatusSender) -> Self {
+        BinaryVersions {
+            yt_dlp: binary_runs(yt_dlp_binary(tx), "--version").await,
+            ffmpeg: binary_runs(ffmpeg_binary(), "-version").await,
+        }
+    }
+
+    // "yt-dlp 2024.08.06 · ffmpeg 6.1.1"
+    fn label(&self) -> String {
+        let version = |result: &Result<String, String>| match result {
+            // ffmpeg responde "ffmpeg version 6.1.1-full_build-www.gyan.dev Copyright ..."
+            Ok(line) => line
+                .strip_prefix("ffmpeg version ")
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|version| version.split('-').next())
+                .unwrap_or(line)
+                .to_string(),
+            Err(_) => "no disponible".to_string(),
+        };
+        format!("yt-dlp {} · ffmpeg {}", version(&self.yt_dlp), version(&self.ffmpeg))
+    }
+}
+
+// Diagnóstico rápido antes de descargar: binarios, conexión con YouTube y destino
+// Con `quiet` solo se muestran las verificaciones fallidas, en stderr
+async fn run_self_check(dest_dir: &str, config: &Config, quiet: bool) -> bool {
+    let (tx, _rx) = status::channel();
+    let versions = BinaryVersions::detect(&tx).await;
+
+    let checks = [
+        ("yt-dlp", versions.yt_dlp),
+        ("ffmpeg", versions.ffmpeg),
+        (
+            "Conexión con YouTube",
+            get_metadata_video(SELF_CHECK_URL, config, &tx)
+                .await
+                .map(|metadata| metadata.title)
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "Destino con permiso de escritura",
+            all_dests_writable(&config.dest_dirs(dest_dir)).await,
+        ),
+    ];
+
+    let mut all_ok = true;
+    for (name, result) in checks {
+        match result {
+            Ok(detail) => {
+                if !quiet {
+                    println!("[OK]    {}: {}", name, detail);
+                }
+            }
+            Err(e) => {
+                all_ok = false;
+                warn!(check = name, error = %e, "verificación inicial fallida");
+                if quiet {
+                    eprintln!("[FALLA] {}: {}", name, e);
+                } else {
+                    println!("[FALLA] {}: {}", name, e);
+                }
+            }
+        }
+    }
+    all_ok
+}
+
+// Última descarga correcta del historial; con `path`, la de ese archivo
+fn undo_candidate(path: Option<&Path>) -> Result<Option<HistoryEntry>, String> {
+    let entries = history::load(Path::new(HISTORY_FILE))?;
+    Ok(entries
+        .into_iter()
+        .rev()
+        .find(|entry| entry.error.is_none() && path.is_none_or(|path| entry.path == path)))
+}
+
+// Borra el archivo, las carpetas que quedaron vacías (sin pasar del destino) y su entrada del historial
+async fn undo_download(entry: &HistoryEntry) -> Result<String, String> {
+    match fs::remove_file(&entry.path).await {
+        Ok(()) => {}
+        // Si ya no estaba, igual se saca del historial
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("No se pudo borrar {:?}: {}", entry.path, e)),
+    }
+
+    // remove_dir falla si la carpeta todavía tiene algo, y ahí se corta
+    let mut removed_dirs = 0;
+    let mut dir = entry.path.parent();
+    while let Some(current) = dir {
+        if current == entry.dest_root || !current.starts_with(&entry.dest_root) {
+            break;
+        }
+        if fs::remove_dir(current).await.is_err() {
+            break;
+        }
+        removed_dirs += 1;
+        dir = current.parent();
+    }
+
+    history::remove(Path::new(HISTORY_FILE), &entry.path)?;
+
+    let mut message = format!("Deshecho: {} - {} ({:?} borrado", entry.artist, entry.title, entry.path);
+    if removed_dirs > 0 {
+        message.push_str(", con su carpeta vacía");
+    }
+    message.push(')');
+    Ok(message)
+}
+
+// --undo: deshace la última descarga del historial, con confirmación
+async fn run_undo() -> Result<(), String> {
+    let Some(entry) = undo_candidate(None)? else {
+        println!("No hay descargas en el historial para deshacer");
+        return Ok(());
+    };
+
+    let question = format!("¿Borrar {:?} ({} - {})?", entry.path, entry.artist, entry.title);
+    if !ask_yes_no(&question).map_err(|e| e.to_string())? {
+        println!("No se borró nada");
+        return Ok(());
+    }
+
+    println!("{}", undo_download(&entry).await?);
+    Ok(())
+}
+
+// --export-manifest: lo que está en `dest_dir` según el historial, para llevarlo a otra máquina
+fn run_export_manifest(path: &Path, dest_dir: Option<&str>) -> Result<(), String> {
+    let dest_dir = dest_dir.ok_or("Indica la carpeta de la biblioteca con --dest o dest_dir en config.toml")?;
+    let entries = history::load(Path::new(HISTORY_FILE))?;
+
+    let manifest = manifest::from_history(&entries, Path::new(dest_dir));
+    manifest::save(path, &manifest)?;
+    println!("{} temas exportados a {:?}", manifest.entries.len(), path);
+    Ok(())
+}
+
+// --import-manifest: agrega al historial lo que falte; sin --dest se usa la carpeta guardada en el manifiesto
+fn run_import_manifest(path: &Path, dest_dir: Option<&str>) -> Result<(), String> {
+    let manifest = manifest::load(path)?;
+    let dest_root = dest_dir.map(PathBuf::from).unwrap_or_else(|| manifest.dest_root.clone());
+
+    let mut entries = history::load(Path::new(HISTORY_FILE))?;
+    let imported = manifest::to_history(&manifest, &dest_root, &entries);
+    let missing = imported.iter().filter(|entry| !entry.path.exists()).count();
+    let count = imported.len();
+    entries.extend(imported);
+    history::save(Path::new(HISTORY_FILE), &entries)?;
+
+    println!("{} temas agregados al historial ({} ya estaban)", count, manifest.entries.len() - count);
+    if missing > 0 {
+        println!("Aviso: {} archivos no están en {:?}; ¿es la carpeta correcta?", missing, dest_root);
+    }
+    Ok(())
+}
+
+// Extensiones que --scan-library toma como temas
+const LIBRARY_EXTENSIONS: [&str; 9] = ["mp3", "m4a", "aac", "flac", "opus", "ogg", "wav", "aiff", "webm"];
+
+// (artista, título, álbum, pista) a partir de la ruta relativa al destino, según los esquemas de nombres:
+// "Artista/Álbum/NN - Título", "Artista/Artista-Título", "Artista/Título" o "Artista-Título"
+fn names_from_path(relative: &Path) -> (String, String, Option<String>, Option<u32>) {
+    let stem = relative.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let dirs: Vec<String> = relative
+        .parent()
+        .map(|parent| parent.iter().map(|dir| dir.to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+
+    let numbered = stem
+        .split_once(" - ")
+        .and_then(|(number, title)| Some((number.parse::<u32>().ok()?, title.to_string())));
+    if let (Some((track, title)), [.., artist, album]) = (numbered, dirs.as_slice()) {
+        return (artist.clone(), title, Some(album.clone()), Some(track));
+    }
+
+    match dirs.last() {
+        Some(artist) => {
+            let title = stem.strip_prefix(&format!("{}-", artist)).unwrap_or(&stem).to_string();
+            (artist.clone(), title, None, None)
+        }
+        None => match stem.split_once('-') {
+            Some((artist, title)) => (artist.trim().to_string(), title.trim().to_string(), None, None),
+            None => (UNKNOWN_ARTIST.to_string(), stem, None, None),
+        },
+    }
+}
+
+// --scan-library: agrega al historial los temas del destino que no bajó esta app (sin enlace).
+// Con detect_duplicates además calcula su huella para que cuenten como duplicados
+async fn scan_library(config: &Config, dest_dir: Option<&str>) -> Result<(), String> {
+    let dest_dir = dest_dir.ok_or("Indica la carpeta de la biblioteca con --dest o dest_dir en config.toml")?;
+    let dest_root = PathBuf::from(dest_dir);
+    let mut entries = history::load(Path::new(HISTORY_FILE))?;
+
+    let (mut files, _) = staging_entries(dest_dir).await;
+    files.sort();
+
+    let mut added = 0;
+    for path in files {
+        let is_audio = path
+            .extension()
+            .is_some_and(|ext| LIBRARY_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()));
+        if !is_audio || entries.iter().any(|entry| entry.path == path) {
+            continue;
+        }
+
+        let relative = path.strip_prefix(&dest_root).unwrap_or(&path);
+        let (artist, title, album, track_number) = names_from_path(relative);
+        let metadata = fs::metadata(&path).await.map_err(|e| format!("{:?}: {}", path, e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|since| since.as_secs())
+            .unwrap_or_else(history::now_secs);
+
+        let fingerprint = if config.detect_duplicates {
+            match fingerprint::compute(&path).await {
+                Ok(fingerprint) => Some(fingerprint),
+                Err(e) => {
+                    println!("Sin huella para {:?}: {}", path, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        println!("{} - {} ({:?})", artist, title, relative);
+        entries.push(HistoryEntry {
+            url: String::new(),
+            path,
+            dest_root: dest_root.clone(),
+            artist,
+            title,
+            bytes: metadata.len(),
+            album,
+            track_number,
+            error: None,
+            fingerprint,
+            downloaded_at: modified,
+        });
+        added += 1;
+    }
+
+    history::save(Path::new(HISTORY_FILE), &entries)?;
+    println!("{} temas agregados al historial", added);
+    Ok(())
+}
+
+// Escribe las etiquetas de cada archivo del historial sin volver a descargarlo
+async fn retag_library(config: &Config, dry_run: bool) -> Result<(), String> {
+    let entries = history::load(Path::new(HISTORY_FILE))?;
+
+    // Los mensajes de estado no se muestran en este modo
+    let (tx, _rx) = status::channel();
+
+    let mut pending = 0;
+    // Sin enlace (agregados con --scan-library) no hay de dónde sacar las etiquetas
+    for entry in entries.iter().filter(|entry| entry.error.is_none() && !entry.url.is_empty()) {
+        if !entry.path.exists() {
+            println!("Falta (¿movido o renombrado?): {:?} ({})", entry.path, entry.url);
+            continue;
+        }
+        if !tags::supports(&entry.path) {
+            println!("Sin soporte para etiquetar este formato: {:?}", entry.path);
+            continue;
+        }
+
+        let info = match get_metadata_json(&entry.url, config, &tx).await {
+            Ok(info) => info,
+            Err(e) => {
+                println!("Sin metadata para {}: {}", entry.url, e);
+                continue;
+            }
+        };
+        let track_tags = info.track_tags(config);
+
+        let changes = match tags::diff(&entry.path, &track_tags) {
+            Ok(changes) => changes,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        if changes.is_empty() {
+            continue;
+        }
+
+        pending += 1;
+        println!("{:?}", entry.path);
+        for change in &changes {
+            println!("  {}", change);
+        }
+
+        if !dry_run {
+            match tags::write(&entry.path, &track_tags) {
+                Ok(()) => println!("  Etiquetado"),
+                Err(e) => println!("  {}", e),
+            }
+        }
+    }
+
+    if pending == 0 {
+        println!("Todas las etiquetas están al día");
+    } else if dry_run {
+        println!("{} archivos por etiquetar. Ejecuta sin --dry-run para aplicar los cambios.", pending);
+    }
+
+    Ok(())
+}
+
+// Compara cada archivo del historial con el nombre que le daría el esquema actual
+async fn repair_library(config: &Config, apply: bool) -> Result<(), String> {
+    let history_path = Path::new(HISTORY_FILE);
+    let mut entries = history::load(history_path)?;
+
+    // Los mensajes de estado no se muestran en este modo
+    let (tx, _rx) = status::channel();
+
+    let mut mismatches = 0;
+    let mut changed = false;
+
+    for entry in entries.iter_mut() {
+        // Descargas fallidas: no hay archivo que revisar
+        if entry.error.is_some() {
+            continue;
+        }
+
+        if !entry.path.exists() {
+            println!("Falta: {:?} ({})", entry.path, entry.url);
+            continue;
+        }
+
+        // Los temas de álbum se ordenan con lo guardado (oembed no conoce el álbum), igual que los que no tienen enlace
+        let metadata = if entry.album.is_some() || entry.url.is_empty() {
+            VideoMetadata {
+                title: entry.title.clone(),
+                author_name: entry.artist.clone(),
+                album: entry.album.clone(),
+                track_number: entry.track_number,
+            }
+        } else {
+            match get_metadata_video(&entry.url, config, &tx).await {
+                Ok(mut metadata) => {
+                    metadata.title = config.clean_title(&metadata.title);
+                    metadata
+                }
+                Err(e) => {
+                    println!("Sin metadata para {}: {}", entry.url, e);
+                    continue;
+                }
+            }
+        };
+
+        let extension = entry
+            .path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "mp3".to_string());
+        let layout = config.library_layout();
+        // Con el nombre original solo se reacomoda la carpeta
+        let expected = match entry.path.file_name() {
+            Some(file_name) if layout.keep_original_name => {
+                artist_dir(&entry.dest_root, &metadata, layout).join(file_name)
+            }
+            _ => expected_path(&entry.dest_root, &metadata, layout, &extension),
+        };
+
+        if expected == entry.path {
+            continue;
+        }
+
+        mismatches += 1;
+        println!("{:?}\n  -> {:?}", entry.path, expected);
+
+        if !apply {
+            continue;
+        }
+
+        if expected.exists() {
+            println!("  El destino ya existe, se omite");
+            continue;
+        }
+
+        if let Some(parent) = expected.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
+        }
+
+        let old_path = entry.path.clone();
+        match move_file(&old_path, &expected).await {
+            Ok(()) => {
+                // Si la carpeta del artista anterior quedó vacía, se elimina
+                if let Some(old_parent) = old_path.parent() {
+                    let _ = fs::remove_dir(old_parent).await;
+                }
+                entry.path = expected;
+                entry.artist = metadata.author_name;
+                entry.title = metadata.title;
+                changed = true;
+                println!("  Corregido");
+            }
+            Err(e) => println!("  Error al mover: {}", e),
+        }
+    }
+
+    if changed {
+        history::save(history_path, &entries)?;
+    }
+
+    if mismatches == 0 {
+        println!("Todos los archivos siguen el esquema de nombres actual");
+    } else if !apply {
+        println!("{} archivos por corregir. Usa --apply para aplicar los cambios.", mismatches);
+    }
+
+    Ok(())
+}
+
+// Un elemento de la cola (sin terminar) para el mismo video o playlist
+fn queued_duplicate(queue: &DownloadQueue, url: &str) -> Option<String> {
+    let key = queue_key(url);
+    queue.snapshot().into_iter().find(|item| queue_key(&item.url) == key).map(|item| item.url)
+}
+
+fn enqueue_url(queue: &DownloadQueue, url: String, dest: Option<String>, force: bool, messages: &mut Vec<String>) {
+    if !force && queued_duplicate(queue, &url).is_some() {
+        messages.push(format!("Already in queue: {} (escribe ! antes del enlace para encolarlo igual)", url));
+        return;
+    }
+    let id = queue.push(url.clone(), dest.clone());
+    let ahead = queue.ahead_of(id).unwrap_or(0);
+    match dest {
+        Some(dest) => messages.push(format!("Queued ({} ahead): {} -> {}", ahead, url, dest)),
+        None => messages.push(format!("Queued ({} ahead): {}", ahead, url)),
+    }
+}
+
+// La carpeta se crea al descargar, pero tiene que colgar de una carpeta existente
+fn validate_dest_dir(dest: &str) -> Result<(), String> {
+    let path = Path::new(dest);
+    if path.exists() {
+        return if path.is_dir() {
+            Ok(())
+        } else {
+            Err(format!("{} no es una carpeta", dest))
+        };
+    }
+
+    let parent_ok = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .map(|ancestor| ancestor.is_dir())
+        .unwrap_or(path.is_relative());
+
+    if parent_ok {
+        Ok(())
+    } else {
+        Err(format!("La carpeta {} no existe y no se puede crear", dest))
+    }
+}
+
+// "!<url>" encola aunque el mismo video ya esté en la cola
+fn split_force(input: &str) -> (&str, bool) {
+    match input.trim().strip_prefix('!') {
+        Some(rest) => (rest.trim_start(), true),
+        None => (input, false),
+    }
+}
+
+// "<url> | <carpeta>" descarga ese enlace en otra carpeta sin cambiar la global
+fn split_dest_override(input: &str) -> Result<(&str, Option<String>), String> {
+    let Some((url, dest)) = input.split_once('|') else {
+        return Ok((input.trim(), None));
+    };
+
+    let dest = dest.trim();
+    if dest.is_empty() {
+        return Err("Falta la carpeta destino después de '|'".to_string());
+    }
+    validate_dest_dir(dest)?;
+
+    Ok((url.trim(), Some(dest.to_string())))
+}
+
+// Lo que hace "Enviar": valida el enlace del input y lo encola (o pregunta si es ambiguo)
+fn submit_input(
+    input: &mut String,
+    queue: &DownloadQueue,
+    messages: &mut Vec<String>,
+    playlist_prompt: &mut Option<(YoutubeUrl, Option<String>, bool)>,
+    allow_other_sites: bool,
+) {
+    if input.trim().is_empty() {
+        return;
+    }
+
+    let (line, force) = split_force(input);
+    let parsed = split_dest_override(line)
+        .and_then(|(url, dest)| Ok((parse_media_url(url, allow_other_sites)?, dest)));
+    match parsed {
+        Ok((MediaUrl::Youtube(parsed), dest)) if parsed.is_ambiguous() => *playlist_prompt = Some((parsed, dest, force)),
+        Ok((MediaUrl::Youtube(parsed), dest)) => {
+            if let Some(url) = resolve_url(&parsed, false) {
+                enqueue_url(queue, url, dest, force, messages);
+            }
+        }
+        Ok((MediaUrl::Other(url), dest)) => enqueue_url(queue, url, dest, force, messages),
+        Err(e) => messages.push(e),
+    }
+    input.clear();
+}
+
+// Archivos y carpetas bajo `output`, incluidas las subcarpetas de cada descarga
+async fn staging_entries(output_dir: &str) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut pending = vec![PathBuf::from(output_dir)];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.file_type().await {
+                Ok(file_type) if file_type.is_dir() => {
+                    pending.push(entry.path());
+                    dirs.push(entry.path());
+                }
+                Ok(file_type) if file_type.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+
+    (files, dirs)
+}
+
+// Lo mismo sin `output/cache`, que guarda las bases de datos y no es temporal
+async fn temporary_entries(output_dir: &str) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let (files, dirs) = staging_entries(output_dir).await;
+    let outside_cache = |path: &PathBuf| !path.starts_with(CACHE_DIR);
+    (
+        files.into_iter().filter(outside_cache).collect(),
+        dirs.into_iter().filter(outside_cache).collect(),
+    )
+}
+
+// Las más profundas primero; remove_dir falla (y se ignora) si todavía tienen algo
+async fn remove_empty_dirs(mut dirs: Vec<PathBuf>) {
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in dirs {
+        let _ = fs::remove_dir(&dir).await;
+    }
+}
+
+// Borra lo que quedó en las carpetas temporales de una sesión anterior
+async fn clear_staging_dirs(output_dir: &str) -> usize {
+    let (files, dirs) = temporary_entries(output_dir).await;
+
+    let mut removed = 0;
+    for path in files {
+        if fs::remove_file(&path).await.is_ok() {
+            removed += 1;
+        }
+    }
+
+    remove_empty_dirs(dirs).await;
+    removed
+}
+
+// Borra los archivos temporales más viejos que `max_age`; devuelve (archivos, bytes)
+async fn sweep_staging_dirs(output_dir: &str, max_age: Duration) -> (usize, u64) {
+    let (files, dirs) = temporary_entries(output_dir).await;
+    // Lo que espera Ctrl+R para moverse se conserva aunque sea viejo
+    let pending: Vec<PathBuf> = PENDING_MOVES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|pending| PathBuf::from(&pending.work_dir))
+        .collect();
+
+    let mut removed = 0;
+    let mut reclaimed = 0;
+
+    for path in files {
+        if pending.iter().any(|work_dir| path.starts_with(work_dir)) {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&path).await else {
+            continue;
+        };
+
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+
+        if age >= max_age && fs::remove_file(&path).await.is_ok() {
+            removed += 1;
+            reclaimed += metadata.len();
+        }
+    }
+
+    remove_empty_dirs(dirs).await;
+    (removed, reclaimed)
+}
+
+const LIBS_DIR: &str = "libs";
+// Caché de yt-dlp dentro de `output`
+const CACHE_DIR: &str = "output/cache";
+// Lo único de `libs` que usa la app; --prune no lo toca sin --force
+const BINARIES_IN_USE: [&str; 3] = ["yt-dlp.exe", "ffmpeg.exe", "last_update"];
+
+async fn total_size(files: &[PathBuf]) -> u64 {
+    let mut total = 0;
+    for file in files {
+        if let Ok(metadata) = fs::metadata(file).await {
+            total += metadata.len();
+        }
+    }
+    total
+}
+
+fn in_use(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| BINARIES_IN_USE.contains(&name))
+}
+
+// --storage: cuánto ocupan `libs`, `output` y la caché; con `prune` además libera espacio
+async fn run_storage(prune: bool, force: bool) -> Result<(), String> {
+    let (libs, _) = staging_entries(LIBS_DIR).await;
+    let (output, _) = staging_entries(STAGING_DIR).await;
+    let (cache, staged): (Vec<PathBuf>, Vec<PathBuf>) =
+        output.into_iter().partition(|file| file.starts_with(CACHE_DIR));
+
+    println!("{}: {}", LIBS_DIR, format_bytes(total_size(&libs).await));
+    for file in &libs {
+        let size = fs::metadata(file).await.map(|m| m.len()).unwrap_or(0);
+        let mark = if in_use(file) { " (en uso)" } else { "" };
+        println!("  {:?}: {}{}", file, format_bytes(size), mark);
+    }
+    println!("{} (temporales): {}", STAGING_DIR, format_bytes(total_size(&staged).await));
+    println!("{} (caché): {}", CACHE_DIR, format_bytes(total_size(&cache).await));
+
+    if !prune {
+        return Ok(());
+    }
+
+    // Con antigüedad cero se van todos los temporales; `output/cache` queda
+    let (mut removed, mut reclaimed) = sweep_staging_dirs(STAGING_DIR, Duration::ZERO).await;
+
+    for file in libs.iter().filter(|file| force || !in_use(file)) {
+        let size = fs::metadata(file).await.map(|m| m.len()).unwrap_or(0);
+        match fs::remove_file(file).await {
+            Ok(()) => {
+                removed += 1;
+                reclaimed += size;
+            }
+            Err(e) => eprintln!("No se pudo borrar {:?}: {}", file, e),
+        }
+    }
+
+    // La caché propia de yt-dlp vive fuera del proyecto; la borra el mismo yt-dlp
+    if !force {
+        let (tx, _rx) = status::channel();
+        let rm_cache = match yt_dlp_binary(&tx).map_err(|e| e.to_string()) {
+            Ok(binary) => Command::new(binary)
+                .arg("--rm-cache-dir")
+                .output()
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|output| {
+                    if output.status.success() {
+                        Ok(())
+                    } else {
+                        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+                    }
+                }),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = rm_cache {
+            eprintln!("No se pudo borrar la caché de yt-dlp: {}", e);
+        }
+    }
+
+    println!("Eliminados {} archivos ({} liberados)", removed, format_bytes(reclaimed));
+    if force {
+        println!("yt-dlp y ffmpeg se vuelven a descargar la próxima vez que se inicie la app");
+    }
+    Ok(())
+}
+
+// pid de una carpeta de trabajo "<id>-<pid>-<n>" (ver job_dir); el id del video puede tener '-'
+fn job_dir_pid(dir_name: &str) -> Option<u32> {
+    let mut parts = dir_name.rsplitn(3, '-');
+    parts.next()?;
+    parts.next()?.parse().ok()
+}
+
+fn is_partial_file_name(name: &str) -> bool {
+    name.ends_with(".part") || name.ends_with(".ytdl") || name.contains(".part-Frag")
+}
+
+// Restos de una descarga cortada
+fn is_orphan_file(path: &Path, len: u64) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+    len == 0 || is_partial_file_name(name)
+}
+
+// Al iniciar: borra de `output` los parciales y archivos vacíos que no son de otra instancia todavía abierta
+async fn repair_staging_dir(output_dir: &str) -> Vec<PathBuf> {
+    let (files, dirs) = temporary_entries(output_dir).await;
+    let mut system = System::new();
+    let mut removed = Vec::new();
+
+    for path in files {
+        let Ok(metadata) = fs::metadata(&path).await else {
+            continue;
+        };
+        if !is_orphan_file(&path, metadata.len()) {
+            continue;
+        }
+
+        let owner = path
+            .strip_prefix(output_dir)
+            .ok()
+            .and_then(|relative| relative.components().next())
+            .and_then(|dir| job_dir_pid(&dir.as_os_str().to_string_lossy()));
+        if let Some(pid) = owner.filter(|pid| *pid != std::process::id()) {
+            let pid = Pid::from_u32(pid);
+            system.refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+            if system.process(pid).is_some() {
+                continue;
+            }
+        }
+
+        if fs::remove_file(&path).await.is_ok() {
+            info!(path = ?path, "archivo huérfano eliminado de output");
+            removed.push(path);
+        }
+    }
+
+    remove_empty_dirs(dirs).await;
+    removed
+}
+
+const STAGING_SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Limpieza periódica de `output`, solo cuando no hay descargas en curso
+async fn run_staging_cleanup(
+    output_dir: &'static str,
+    max_age: Duration,
+    active_downloads: Arc<AtomicUsize>,
+    tx: StatusSender,
+) {
+    let mut ticker = tokio::time::interval(STAGING_SWEEP_INTERVAL);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        if active_downloads.load(Ordering::SeqCst) > 0 {
+            continue;
+        }
+
+        let (removed, reclaimed) = sweep_staging_dirs(output_dir, max_age).await;
+        if removed > 0 {
+            let _ = tx.send(format!(
+                "Limpieza de output: {} archivos viejos eliminados ({} liberados)",
+                removed, format_bytes(reclaimed)
+            ));
+        }
+    }
+}
+
+fn ask_yes_no(question: &str) -> io::Result<bool> {
+    println!("{} [s/N]", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "s" | "si" | "sí" | "y" | "yes"))
+}
+
+struct UiLayout {
+    messages: Rect,
+    queue: Option<Rect>,
+    input: Rect,
+    button: Option<Rect>,
+    status_bar: Option<Rect>,
+}
+
+// En terminales chicas se quita el margen y la fila del botón
+fn ui_layout(size: Rect) -> UiLayout {
+    let margin = if size.height >= 8 && size.width >= 24 { 1 } else { 0 };
+    let show_button = size.height >= 12;
+    let show_status_bar = size.height >= 10;
+
+    let mut constraints = vec![Constraint::Min(0), Constraint::Length(3)];
+    if show_button {
+        constraints.push(Constraint::Length(3));
+    }
+    if show_status_bar {
+        constraints.push(Constraint::Length(1));
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(margin)
+        .constraints(constraints)
+        .split(size);
+
+    // La cola va a la derecha de los mensajes si hay ancho suficiente
+    let (messages, queue) = if size.width >= 70 {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+            .split(chunks[0]);
+        (columns[0], Some(columns[1]))
+    } else {
+        (chunks[0], None)
+    };
+
+    UiLayout {
+        messages,
+        queue,
+        input: chunks[1],
+        button: if show_button { chunks.get(2).copied() } else { None },
+        status_bar: if show_status_bar { chunks.last().copied() } else { None },
+    }
+}
+
+// Valida el borrador y lo aplica a las próximas descargas; con `persist` también se guarda en config.toml
+fn apply_settings(form: &SettingsForm, settings: &RwLock<Config>, persist: bool) -> Result<String, String> {
+    form.draft.validate()?;
+
+    match form.draft.dest_dir.as_deref().map(str::trim) {
+        Some(dest) if !dest.is_empty() => validate_dest_dir(dest)?,
+        _ => return Err("el destino no puede quedar vacío".to_string()),
+    }
+
+    *settings.write().unwrap() = form.draft.clone();
+
+    if persist {
+        form.draft.save(Path::new(CONFIG_FILE))?;
+        return Ok("Ajustes aplicados y guardados en config.toml".to_string());
+    }
+    Ok("Ajustes aplicados a las próximas descargas".to_string())
+}
+
+// Editor para Ctrl+G: $VISUAL o $EDITOR, que pueden traer argumentos (ej. "code --wait")
+fn config_editor() -> Vec<String> {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .and_then(|editor| shlex::split(&editor))
+        .filter(|parts| !parts.is_empty())
+        .unwrap_or_else(|| vec![if cfg!(windows) { "notepad" } else { "vi" }.to_string()])
+}
+
+// Abre config.toml en el editor y lo vuelve a leer; si quedó inválido se sigue con la configuración anterior
+fn edit_config_file(settings: &RwLock<Config>) -> Result<String, String> {
+    let path = Path::new(CONFIG_FILE);
+    if !path.exists() {
+        Config::default().save(path)?;
+    }
+
+    let editor = config_editor();
+    let status = std::process::Command::new(&editor[0])
+        .args(&editor[1..])
+        .arg(path)
+        .status()
+        .map_err(|e| format!("No se pudo abrir el editor {:?}: {}", editor[0], e))?;
+    if !status.success() {
+        return Err(format!("El editor terminó con error ({}); no se recargó config.toml", status));
+    }
+
+    let mut config = Config::load(path).map_err(|e| format!("{}; se mantiene la configuración anterior", e))?;
+    let mut current = settings.write().unwrap();
+    // El destino elegido al iniciar sigue si el archivo no tiene uno
+    if config.dest_dir.is_none() {
+        config.dest_dir = current.dest_dir.clone();
+    }
+    *current = config;
+    Ok("config.toml recargado; se aplica a las próximas descargas".to_string())
+}
+
+// Guarda solo formato y calidad, sin arrastrar a config.toml lo que vino por línea de comandos
+fn persist_audio_choice(config: &Config) -> Result<(), String> {
+    let mut saved = Config::load(Path::new(CONFIG_FILE))?;
+    saved.audio_format = config.audio_format.clone();
+    saved.audio_quality = config.audio_quality.clone();
+    saved.save(Path::new(CONFIG_FILE))
+}
+
+// Ctrl+L: los mensajes muestran las rutas desde la carpeta destino ("Artista/Tema.mp3").
+// Las rutas llegan con Display o con Debug (barras invertidas escapadas en Windows)
+fn relative_paths(message: &str, roots: &[PathBuf]) -> String {
+    let mut roots: Vec<&PathBuf> = roots.iter().filter(|root| !root.as_os_str().is_empty()).collect();
+    // La más larga primero, por si un destino está dentro de otro
+    roots.sort_by_key(|root| std::cmp::Reverse(root.as_os_str().len()));
+
+    let mut message = message.to_string();
+    for root in roots {
+        let plain = root.display().to_string();
+        let debug = format!("{:?}", root);
+        let debug = debug.trim_matches('"');
+        for prefix in [format!("{}/", plain), format!("{}\\", plain), format!("{}\\\\", debug)] {
+            message = message.replace(&prefix, "");
+        }
+    }
+    message
+}
+
+// Resumen de los ajustes de audio activos para la barra de estado
+fn audio_settings_label(config: &Config) -> String {
+    let sample_rate = match config.audio_sample_rate {
+        Some(rate) => format!("{} Hz", rate),
+        None => "Hz original".to_string(),
+    };
+    let channels = match config.audio_channels {
+        Some(1) => "mono",
+        Some(_) => "estéreo",
+        None => "canales originales",
+    };
+
+    if let Some(container) = &config.remux_container {
+        return format!(" sin recodificar → {} · {} · {}", container, sample_rate, channels);
+    }
+
+    let format = config.effective_audio_format();
+    format!(
+        " {} → .{} (calidad {}) · {} · {}",
+        format,
+        audio_extension(format),
+        config.audio_quality,
+        sample_rate,
+        channels
+    )
+}
+
+fn rect_contains(rect: Rect, column: u16, row: u16) -> bool {
+    column >= rect.x && column < rect.x + rect.width && row >= rect.y && row < rect.y + rect.height
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+                .as_ref(),
+        )
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+                .as_ref(),
+        )
+        .split(vertical[1])[1]
+}
+
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+// `fraction` entre 0 y 1
+fn progress_bar(fraction: f32) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * PROGRESS_BAR_WIDTH as f32).round() as usize).min(PROGRESS_BAR_WIDTH);
+    format!("{}{}", "█".repeat(filled), "░".repeat(PROGRESS_BAR_WIDTH - filled))
+}
+
+fn run_ui(
+    queue: Arc<DownloadQueue>,
+    status_tx: StatusSender,
+    status_rx: Receiver<StatusEvent>,
+    settings: Arc<RwLock<Config>>,
+    versions: BinaryVersions,
+) -> io::Result<()> {
+    // Setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut input = String::new();
+    let mut messages: Vec<String> = Vec::new();
+    let mut button_focused = false;
+    let mut busy_phase: Option<String> = None;
+    // Elemento elegido en el panel de la cola (↑/↓); Supr lo cancela
+    let mut queue_selected: usize = 0;
+
+    // Unidades extraíbles que se eligen como destino con Alt+1..9
+    let mut disks = get_disk_info().unwrap_or_default();
+
+    // Ruta de la última descarga terminada (Ctrl+Y la copia)
+    let mut last_download: Option<PathBuf> = None;
+    // Ctrl+L: rutas relativas al destino en los mensajes (solo al mostrarlas)
+    let mut show_relative_paths = false;
+    // Lotes en curso; una descarga suelta no muestra indicador
+    let mut batches: Vec<BatchProgress> = Vec::new();
+    // Estado de cada elemento en curso, según los avisos del worker
+    let mut item_states: HashMap<u64, ItemState> = HashMap::new();
+    let mut spinner_frame = 0;
+    // Se leen al abrir la interfaz
+    let (refresh_interval, progress_style) = {
+        let config = settings.read().unwrap();
+        (Duration::from_millis(config.ui_refresh_ms), config.progress_style)
+    };
+
+    // Popup de metadata (Ctrl+P): None = cerrado, Some(None) = cargando
+    let runtime = tokio::runtime::Handle::current();
+    let (metadata_tx, metadata_rx) = mpsc::channel::<Result<VideoInfo, String>>();
+    let mut metadata_popup: Option<Option<Result<VideoInfo, String>>> = None;
+
+    // Pantalla de ajustes (Ctrl+O) con una copia editable de la configuración
+    let mut settings_form: Option<SettingsForm> = None;
+
+    // Enlace con video y playlist a la espera de que el usuario elija
+    let mut playlist_prompt: Option<(YoutubeUrl, Option<String>, bool)> = None;
+
+    // Descarga a deshacer (Ctrl+Z), a la espera de confirmación
+    let mut undo_prompt: Option<HistoryEntry> = None;
+
+    // Descargas esperando qué hacer con un nombre repetido (collision_confirm_after); se contestan en orden
+    let mut collision_prompts: VecDeque<CollisionPrompt> = VecDeque::new();
+
+    // Canales fuera de channel_allowlist a confirmar antes de descargar
+    let mut channel_prompts: VecDeque<ChannelPrompt> = VecDeque::new();
+
+    loop {
+        // Leer estados desde el worker sin bloquear (try_recv)
+        while let Ok(event) = status_rx.try_recv() {
+            match event {
+                StatusEvent::Message(st) | StatusEvent::Error(st) => {
+                    messages.push(st);
+                    if messages.len() > 300 {
+                        messages.drain(0..(messages.len() - 300));
+                    }
+                }
+                StatusEvent::Busy(phase) => busy_phase = Some(phase),
+                StatusEvent::Idle => busy_phase = None,
+                StatusEvent::Downloaded(path) => last_download = Some(path),
+                StatusEvent::Batch(progress) => {
+                    batches.retain(|batch| batch.key != progress.key);
+                    if !progress.is_finished() {
+                        batches.push(progress);
+                    }
+                }
+                // Los avisos fuera de orden (de una descarga ya terminada) se descartan
+                StatusEvent::Item(id, state) => {
+                    let current = item_states.entry(id).or_default();
+                    if current.can_become(&state) {
+                        *current = state;
+                    }
+                }
+                StatusEvent::Collision(prompt) => collision_prompts.push_back(prompt),
+                StatusEvent::Channel(prompt) => channel_prompts.push_back(prompt),
+            }
+        }
+        spinner_frame = (spinner_frame + 1) % SPINNER_FRAMES.len();
+
+        if let Ok(result) = metadata_rx.try_recv() {
+            if metadata_popup.is_some() {
+                metadata_popup = Some(Some(result));
+            }
+        }
+
+        // Dibujar UI
+        terminal.draw(|f| {
+            let size = f.size();
+
+            let layout = ui_layout(size);
+
+            let roots = if show_relative_paths {
+                let config = settings.read().unwrap();
+                config.dest_dirs(config.dest_dir.as_deref().unwrap_or_default())
+            } else {
+                Vec::new()
+            };
+            let text: Vec<Spans> = messages
+                .iter()
+                .rev()
+                .map(|m| Spans::from(Span::raw(relative_paths(m, &roots))))
+                .collect();
+
+            let messages_title = match &busy_phase {
+                Some(phase) => format!("Mensajes (recientes)  {} {}...", SPINNER_FRAMES[spinner_frame], phase),
+                None => "Mensajes (recientes)".to_string(),
+            };
+
+            let messages_block = Paragraph::new(text)
+                .style(
+                    Style::default()
+                    .bg(Color::Rgb(66, 74, 118))
+                    .fg(Color::Rgb(167, 187, 236))
+                )
+                .block(
+                    Block::default()
+                    .borders(Borders::ALL)
+                    .title(messages_title)
+                );
+            f.render_widget(messages_block, layout.messages);
+
+            if let Some(queue_area) = layout.queue {
+                let snapshot = queue.snapshot();
+                item_states.retain(|id, _| snapshot.iter().any(|item| item.id == *id));
+                let mut queue_state = ListState::default();
+                if !snapshot.is_empty() {
+                    queue_state.select(Some(queue_selected.min(snapshot.len() - 1)));
+                }
+
+                let items: Vec<ListItem> = snapshot
+                    .iter()
+                    .enumerate()
+                    .map(|(position, item)| {
+                        let title = item.title.as_deref().unwrap_or(&item.url);
+                        let name = match item.kind {
+                            Some(kind) => format!("({}) {}", kind.label(), title),
+                            None => title.to_string(),
+                        };
+                        let label = match item_states.get(&item.id) {
+                            Some(ItemState::Downloading { pct: Some(pct) }) if progress_style == ProgressStyle::Blocks => {
+                                format!("▶ [{} {:.0}%] {}", progress_bar(pct / 100.0), pct, name)
+                            }
+                            Some(state) if *state != ItemState::Queued => format!("▶ [{}] {}", state.label(), name),
+                            _ if item.started => format!("▶ {}", name),
+                            _ => format!("{} ahead: {}", position, name),
+                        };
+                        ListItem::new(label)
+                    })
+                    .collect();
+
+                let queue_list = List::new(items)
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::Rgb(167, 187, 236))
+                    )
+                    .highlight_style(Style::default().fg(Color::Black).bg(Color::Rgb(167, 187, 236)))
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Cola (↑↓ elegir, Supr cancelar, Ctrl+K música/voz)")
+                    );
+                f.render_stateful_widget(queue_list, queue_area, &mut queue_state);
+            }
 
             let input_block = Paragraph::new(input.as_ref())
                 .style(
@@ -396,9 +4359,9 @@ fn run_ui(download_tx: tokio_mpsc::Sender<String>, status_rx: Receiver<String>)
                 .block(
                     Block::default()
                     .borders(Borders::ALL)
-                    .title("URL: https://www.youtube.com/watch?v=(ID del video)")
+                    .title("URL: https://www.youtube.com/watch?v=(ID del video)  [ | carpeta destino ]  (! al inicio: aunque ya esté en cola)")
                 );
-            f.render_widget(input_block, chunks[1]);
+            f.render_widget(input_block, layout.input);
 
             let button_style = if button_focused {
                 Style::default()
@@ -411,34 +4374,595 @@ fn run_ui(download_tx: tokio_mpsc::Sender<String>, status_rx: Receiver<String>)
                 .fg(Color::Rgb(167, 187, 236))
             };
 
-            let button = Paragraph::new("   [ Enviar ]: Enter   [ Salir ]: Ctrl+C / Esc   ")
+            // Con foco el botón indica que se activa con Enter o Espacio
+            let button_text = if button_focused {
+                " > [ Enviar ] <: Enter / Espacio   [ Metadata ]: Ctrl+P   [ Ajustes ]: Ctrl+O   [ config.toml ]: Ctrl+G   [ Pausa ]: Ctrl+D   [ Copiar ruta ]: Ctrl+Y   [ Formato ]: [ ]   [ Calidad ]: - +   [ Salir ]: Ctrl+C / Esc   "
+            } else {
+                "   [ Enviar ]: Enter   [ Metadata ]: Ctrl+P   [ Ajustes ]: Ctrl+O   [ config.toml ]: Ctrl+G   [ Pausa ]: Ctrl+D   [ Copiar ruta ]: Ctrl+Y   [ Formato ]: [ ]   [ Calidad ]: - +   [ Salir ]: Ctrl+C / Esc   "
+            };
+            let button = Paragraph::new(button_text)
                 .style(button_style)
-                .block(Block::default().borders(Borders::ALL));
+                .block(Block::default().borders(Borders::ALL).title("Tab: foco en el botón"));
+
+            if let Some(button_area) = layout.button {
+                f.render_widget(button, button_area);
+            }
+
+            if let Some(status_area) = layout.status_bar {
+                let mut status_spans = Vec::new();
+                if queue.is_paused() {
+                    status_spans.push(Span::styled(
+                        " PAUSED ",
+                        Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+                    ));
+                    status_spans.push(Span::raw(" "));
+                }
+                if settings.read().unwrap().prefer_free_formats {
+                    status_spans.push(Span::styled(
+                        " FORMATOS LIBRES ",
+                        Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD),
+                    ));
+                    status_spans.push(Span::raw(" "));
+                }
+                for batch in &batches {
+                    let label = match progress_style {
+                        ProgressStyle::Text => batch.label(),
+                        ProgressStyle::Blocks => format!(
+                            "{} {}",
+                            progress_bar(batch.done as f32 / batch.total.max(1) as f32),
+                            batch.label()
+                        ),
+                    };
+                    status_spans.push(Span::styled(label, Style::default().fg(Color::White).add_modifier(Modifier::BOLD)));
+                    status_spans.push(Span::raw("  "));
+                }
+                let active_dest = settings.read().unwrap().dest_dir.clone().unwrap_or_default();
+                for (index, disk) in disks.iter().take(9).enumerate() {
+                    let mount = disk.mount_point.display().to_string();
+                    let style = if mount == active_dest {
+                        Style::default().fg(Color::Black).bg(Color::Green)
+                    } else {
+                        Style::default()
+                    };
+                    status_spans.push(Span::styled(format!("Alt+{} {}", index + 1, mount), style));
+                    status_spans.push(Span::raw("  "));
+                }
+                status_spans.push(Span::raw(audio_settings_label(&settings.read().unwrap())));
+                status_spans.push(Span::raw("  "));
+                status_spans.push(Span::styled(versions.label(), Style::default().add_modifier(Modifier::DIM)));
+
+                let status_bar = Paragraph::new(Spans::from(status_spans))
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::Rgb(167, 187, 236))
+                    );
+                f.render_widget(status_bar, status_area);
+            }
+
+            if let Some(popup) = &metadata_popup {
+                let lines: Vec<Spans> = match popup {
+                    None => vec![Spans::from("Obteniendo metadata...")],
+                    Some(Ok(info)) => {
+                        let mut lines = vec![
+                            Spans::from(format!("Título:   {}", info.title)),
+                            Spans::from(format!("Autor:    {}", info.author())),
+                            Spans::from(format!("Duración: {}", info.duration_label())),
+                        ];
+                        let languages = info.audio_languages();
+                        if languages.len() > 1 {
+                            let active = settings.read().unwrap().audio_language.clone();
+                            lines.push(Spans::from(""));
+                            lines.push(Spans::from("Pistas de audio (número: elegir, 0: la original):"));
+                            for (index, (language, original)) in languages.iter().take(9).enumerate() {
+                                let marker = if active.as_deref() == Some(language.as_str()) { "▶" } else { " " };
+                                let note = if *original { " (original)" } else { "" };
+                                lines.push(Spans::from(format!("{} {} {}{}", marker, index + 1, language, note)));
+                            }
+                        }
+                        lines
+                    }
+                    Some(Err(e)) => vec![Spans::from(format!("Error: {}", e))],
+                };
+
+                let area = centered_rect(60, 30, size);
+                let popup_block = Paragraph::new(lines)
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::White)
+                    )
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Metadata (Esc/Enter: cerrar)")
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(popup_block, area);
+            }
+
+            if let Some(form) = &settings_form {
+                let lines: Vec<Spans> = form
+                    .lines()
+                    .into_iter()
+                    .map(|(selected, text)| {
+                        let style = if selected {
+                            Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default()
+                        };
+                        Spans::from(Span::styled(text, style))
+                    })
+                    .collect();
+
+                let area = centered_rect(70, 50, size);
+                let settings_block = Paragraph::new(lines)
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::White)
+                    )
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Ajustes (↑↓ campo, ←→ cambiar, Enter aplicar, Ctrl+S guardar, Esc cancelar)")
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(settings_block, area);
+            }
+
+            if let Some(entry) = &undo_prompt {
+                let area = centered_rect(60, 20, size);
+                let prompt_block = Paragraph::new(vec![
+                    Spans::from(format!("{} - {}", entry.artist, entry.title)),
+                    Spans::from(entry.path.display().to_string()),
+                    Spans::from("¿Borrar el archivo y sacarlo del historial? [S]í / [N]o"),
+                ])
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::White)
+                    )
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Deshacer la última descarga")
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(prompt_block, area);
+            }
+
+            if let Some(prompt) = collision_prompts.front() {
+                let area = centered_rect(60, 20, size);
+                let prompt_block = Paragraph::new(vec![
+                    Spans::from(prompt.path.display().to_string()),
+                    Spans::from(format!("Ya hay {} copias con este nombre; la nueva sería la _{}.", prompt.suffix, prompt.suffix)),
+                    Spans::from("[S]obrescribir / [O]mitir / [R]enombrar"),
+                ])
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::White)
+                    )
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Nombre repetido (Esc: renombrar)")
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(prompt_block, area);
+            } else if let Some(prompt) = channel_prompts.front() {
+                let area = centered_rect(60, 20, size);
+                let prompt_block = Paragraph::new(vec![
+                    Spans::from(prompt.url.clone()),
+                    Spans::from(format!("El canal {} no está en la lista permitida.", prompt.author)),
+                    Spans::from("¿Descargar igual? [S]í / [N]o"),
+                ])
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::White)
+                    )
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Canal desconocido (Esc: no)")
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(prompt_block, area);
+            }
 
-            f.render_widget(button, chunks[2]);
+            if playlist_prompt.is_some() {
+                let area = centered_rect(60, 20, size);
+                let prompt_block = Paragraph::new(vec![
+                    Spans::from("Este enlace es parte de una playlist."),
+                    Spans::from("¿Descargar [S]olo este video o la [P]laylist completa?"),
+                ])
+                    .style(
+                        Style::default()
+                        .bg(Color::Rgb(66, 74, 118))
+                        .fg(Color::White)
+                    )
+                    .block(
+                        Block::default()
+                        .borders(Borders::ALL)
+                        .title("Playlist detectada (Esc: cancelar)")
+                    );
+                f.render_widget(Clear, area);
+                f.render_widget(prompt_block, area);
+            }
         })?;
 
         // Eventos (poll)
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
+        if event::poll(refresh_interval)? {
+            let event = event::read()?;
+            if let Event::Resize(_, _) = event {
+                // Recalcular el layout con el nuevo tamaño
+                terminal.autoresize()?;
+                continue;
+            }
+            if let Event::Mouse(mouse) = event {
+                // Con un popup abierto los clics se ignoran
+                if mouse.kind != MouseEventKind::Down(MouseButton::Left)
+                    || playlist_prompt.is_some()
+                    || undo_prompt.is_some()
+                    || !collision_prompts.is_empty()
+                    || !channel_prompts.is_empty()
+                    || metadata_popup.is_some()
+                    || settings_form.is_some()
+                {
+                    continue;
+                }
+                let layout = ui_layout(terminal.size()?);
+                if let Some(button_area) = layout.button {
+                    if rect_contains(button_area, mouse.column, mouse.row) {
+                        button_focused = true;
+                        submit_input(
+                            &mut input,
+                            &queue,
+                            &mut messages,
+                            &mut playlist_prompt,
+                            settings.read().unwrap().allow_other_sites,
+                        );
+                        continue;
+                    }
+                }
+                if rect_contains(layout.input, mouse.column, mouse.row) {
+                    button_focused = false;
+                }
+                continue;
+            }
+            if let Event::Key(key) = event {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
+                if let Some((parsed, dest, force)) = &playlist_prompt {
+                    let choice = match key.code {
+                        KeyCode::Char('s') | KeyCode::Char('S') => Some(resolve_url(parsed, false)),
+                        KeyCode::Char('p') | KeyCode::Char('P') => Some(resolve_url(parsed, true)),
+                        KeyCode::Esc => Some(None),
+                        _ => None,
+                    };
+                    if let Some(url) = choice {
+                        match url {
+                            Some(url) => enqueue_url(&queue, url, dest.clone(), *force, &mut messages),
+                            None => messages.push("Descarga cancelada".to_string()),
+                        }
+                        playlist_prompt = None;
+                    }
+                    continue;
+                }
+                if !collision_prompts.is_empty() {
+                    let choice = match key.code {
+                        KeyCode::Char('s') | KeyCode::Char('S') => Some(CollisionChoice::Overwrite),
+                        KeyCode::Char('o') | KeyCode::Char('O') => Some(CollisionChoice::Skip),
+                        KeyCode::Char('r') | KeyCode::Char('R') | KeyCode::Esc => Some(CollisionChoice::Rename),
+                        _ => None,
+                    };
+                    if let Some(choice) = choice {
+                        if let Some(prompt) = collision_prompts.pop_front() {
+                            // Si la descarga se canceló mientras tanto nadie espera la respuesta
+                            let _ = prompt.reply.send(choice);
+                        }
+                    }
+                    continue;
+                }
+                if !channel_prompts.is_empty() {
+                    let accept = match key.code {
+                        KeyCode::Char('s') | KeyCode::Char('S') => Some(true),
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => Some(false),
+                        _ => None,
+                    };
+                    if let Some(accept) = accept {
+                        if let Some(prompt) = channel_prompts.pop_front() {
+                            let _ = prompt.reply.send(accept);
+                        }
+                    }
+                    continue;
+                }
+                if let Some(entry) = &undo_prompt {
+                    match key.code {
+                        KeyCode::Char('s') | KeyCode::Char('S') => {
+                            let entry = entry.clone();
+                            let status_tx = status_tx.clone();
+                            runtime.spawn(async move {
+                                match undo_download(&entry).await {
+                                    Ok(message) => {
+                                        let _ = status_tx.send(message);
+                                    }
+                                    Err(e) => {
+                                        let _ = status_tx.error(e);
+                                    }
+                                }
+                            });
+                            // Solo se puede deshacer la última
+                            last_download = None;
+                            undo_prompt = None;
+                        }
+                        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            messages.push("No se borró nada".to_string());
+                            undo_prompt = None;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(popup) = &metadata_popup {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Enter => metadata_popup = None,
+                        // El idioma elegido vale para las próximas descargas de la sesión
+                        KeyCode::Char(c @ '0'..='9') => {
+                            let languages = match popup {
+                                Some(Ok(info)) => info.audio_languages(),
+                                _ => Vec::new(),
+                            };
+                            if languages.len() > 1 {
+                                let choice = match c {
+                                    '0' => Some(None),
+                                    _ => languages.get(c as usize - '1' as usize).map(|(language, _)| Some(language.clone())),
+                                };
+                                if let Some(language) = choice {
+                                    messages.push(match &language {
+                                        Some(language) => format!("Pista de audio: {}", language),
+                                        None => "Pista de audio: la original".to_string(),
+                                    });
+                                    settings.write().unwrap().audio_language = language;
+                                    metadata_popup = None;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(form) = settings_form.as_mut() {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    let persist = match key.code {
+                        KeyCode::Enter => Some(false),
+                        KeyCode::Char('s') if ctrl => Some(true),
+                        _ => None,
+                    };
+                    if let Some(persist) = persist {
+                        match apply_settings(form, &settings, persist) {
+                            Ok(message) => {
+                                messages.push(message);
+                                settings_form = None;
+                            }
+                            Err(e) => messages.push(format!("Ajustes inválidos: {}", e)),
+                        }
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Esc => settings_form = None,
+                        KeyCode::Up | KeyCode::BackTab => form.previous(),
+                        KeyCode::Down | KeyCode::Tab => form.next(),
+                        KeyCode::Left => form.change(false),
+                        KeyCode::Right => form.change(true),
+                        KeyCode::Char(' ') if !form.edits_text() => form.change(true),
+                        KeyCode::Char(c) => form.push_char(c),
+                        KeyCode::Backspace => form.pop_char(),
+                        _ => {}
+                    }
+                    continue;
+                }
                 match key.code {
                     KeyCode::Esc => {
                         // Salir limpiamente
                         disable_raw_mode()?;
-                        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                        execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
                         terminal.show_cursor()?;
                         return Ok(());
                     }
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                          // Salir limpiamente con Ctrl+C
                         disable_raw_mode()?;
-                        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                        execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
                         terminal.show_cursor()?;
                         return Ok(());
                     }
+                    // La terminal vuelve a la normal mientras el editor está abierto
+                    KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        disable_raw_mode()?;
+                        execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+                        let result = edit_config_file(&settings);
+                        enable_raw_mode()?;
+                        execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+                        terminal.clear()?;
+                        messages.push(result.unwrap_or_else(|e| e));
+                    }
+                    // Pausa: no se empiezan descargas nuevas, la que está en curso termina
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let paused = !queue.is_paused();
+                        queue.set_paused(paused);
+                        if paused {
+                            messages.push("Cola en pausa; las descargas en curso terminan (Ctrl+D reanuda)".to_string());
+                        } else {
+                            messages.push("Cola reanudada".to_string());
+                        }
+                    }
+                    KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        match &last_download {
+                            Some(path) => match copy_to_clipboard(&path.display().to_string()) {
+                                Ok(()) => messages.push(format!("Ruta copiada al portapapeles: {}", path.display())),
+                                Err(e) => messages.push(e),
+                            },
+                            None => messages.push("Todavía no terminó ninguna descarga; no hay ruta para copiar".to_string()),
+                        }
+                    }
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let pending = std::mem::take(&mut *PENDING_MOVES.lock().unwrap());
+                        if pending.is_empty() {
+                            messages.push("No hay archivos pendientes de mover".to_string());
+                        } else {
+                            messages.push(format!("Reintentando mover {} archivo(s)...", pending.len()));
+                            let status_tx = status_tx.clone();
+                            runtime.spawn(async move {
+                                for mut pending in pending {
+                                    if let Err(e) = finish_pending_move(&mut pending, &status_tx).await {
+                                        let _ = status_tx.send(format!("No se pudo mover {}: {}", pending.metadata.title, e));
+                                        PENDING_MOVES.lock().unwrap().push(pending);
+                                    }
+                                }
+                            });
+                        }
+                    }
+                    KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let candidate = match &last_download {
+                            Some(path) => undo_candidate(Some(path)),
+                            None => Ok(None),
+                        };
+                        match candidate {
+                            Ok(Some(entry)) => undo_prompt = Some(entry),
+                            Ok(None) => messages.push("No hay ninguna descarga de esta sesión para deshacer".to_string()),
+                            Err(e) => messages.push(e),
+                        }
+                    }
+                    KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let skipped = std::mem::take(&mut *LOW_BITRATE_SKIPPED.lock().unwrap());
+                        if skipped.is_empty() {
+                            messages.push("No hay temas omitidos por bitrate bajo".to_string());
+                        }
+                        for (url, dest) in skipped {
+                            messages.push(format!("Encolado sin bitrate mínimo: {}", url));
+                            queue.push_any_bitrate(url, Some(dest));
+                        }
+                    }
+                    KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let (downloads, bytes) = stats::session();
+                        stats::reset_session();
+                        messages.push(format!(
+                            "Contadores de la sesión en cero (eran {} descargas, {}); Ctrl+D reanuda la cola si está en pausa",
+                            downloads,
+                            format_bytes(bytes)
+                        ));
+                    }
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let count = metadata_cache().clear();
+                        messages.push(format!("Caché de metadata vaciada ({} videos); se vuelve a pedir al descargar", count));
+                    }
+                    KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        show_relative_paths = !show_relative_paths;
+                        messages.push(if show_relative_paths {
+                            "Rutas relativas al destino (Ctrl+L para verlas completas)".to_string()
+                        } else {
+                            "Rutas completas".to_string()
+                        });
+                    }
+                    KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        match stats::load(Path::new(STATS_FILE)) {
+                            Ok(days) if days.is_empty() => messages.push("Todavía no hay estadísticas de descargas".to_string()),
+                            Ok(days) => {
+                                messages.push(format!("Descargas de los últimos {} días con actividad:", stats::SUMMARY_DAYS));
+                                for day in stats::last_days(&days, stats::SUMMARY_DAYS) {
+                                    messages.push(format!("  {}: {} descarga(s), {}", day.date, day.downloads, format_bytes(day.bytes)));
+                                }
+                            }
+                            Err(e) => messages.push(e),
+                        }
+                    }
+                    // Alt+N elige la N-ésima unidad extraíble como destino de las próximas descargas
+                    KeyCode::Char(c @ '1'..='9') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        disks = get_disk_info().unwrap_or_default();
+                        let index = c as usize - '1' as usize;
+                        match disks.get(index) {
+                            Some(disk) => {
+                                let mount = disk.mount_point.display().to_string();
+                                settings.write().unwrap().dest_dir = Some(mount.clone());
+                                messages.push(format!("Destino activo: {} ({})", mount, disk.name));
+                            }
+                            None => messages.push(format!("No hay una unidad extraíble en Alt+{}", c)),
+                        }
+                    }
+                    KeyCode::Up => queue_selected = queue_selected.saturating_sub(1),
+                    KeyCode::Down => {
+                        let len = queue.snapshot().len();
+                        queue_selected = (queue_selected + 1).min(len.saturating_sub(1));
+                    }
+                    KeyCode::Delete => {
+                        let selected = queue.snapshot().get(queue_selected).map(|item| item.id);
+                        match selected.and_then(|id| queue.cancel(id)) {
+                            Some(item) => messages.push(format!("Cancelled: {}", item.url)),
+                            None => messages.push("No hay ningún elemento elegido en la cola".to_string()),
+                        }
+                    }
+                    // Auto -> música -> voz -> auto
+                    KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let selected = queue.snapshot().get(queue_selected).cloned();
+                        match selected {
+                            Some(item) => {
+                                let kind = match item.kind {
+                                    None => Some(ContentKind::Music),
+                                    Some(ContentKind::Music) => Some(ContentKind::Speech),
+                                    Some(ContentKind::Speech) => None,
+                                };
+                                if queue.set_kind(item.id, kind) {
+                                    let label = kind.map(ContentKind::label).unwrap_or("automático");
+                                    messages.push(format!("Tipo de {}: {}", item.url, label));
+                                } else {
+                                    messages.push("La descarga ya empezó; no se puede cambiar su tipo".to_string());
+                                }
+                            }
+                            None => messages.push("No hay ningún elemento elegido en la cola".to_string()),
+                        }
+                    }
+                    KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        settings_form = Some(SettingsForm::new(settings.read().unwrap().clone()));
+                    }
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let url = input.trim().to_string();
+                        if !url.is_empty() {
+                            metadata_popup = Some(None);
+                            let metadata_tx = metadata_tx.clone();
+                            let status_tx = status_tx.clone();
+                            let config = settings.read().unwrap().clone();
+                            runtime.spawn(async move {
+                                let _ = metadata_tx
+                                    .send(get_metadata_json(&url, &config, &status_tx).await.map_err(|e| e.to_string()));
+                            });
+                        }
+                    }
+                    // Con el input vacío: [ ] cambian el formato y - + la calidad de las próximas descargas
+                    KeyCode::Char(c @ ('[' | ']' | '-' | '+')) if input.is_empty() => {
+                        let mut current = settings.write().unwrap();
+                        match c {
+                            '[' => cycle_audio_format(&mut current, false),
+                            ']' => cycle_audio_format(&mut current, true),
+                            '-' => step_audio_quality(&mut current, false),
+                            _ => step_audio_quality(&mut current, true),
+                        }
+                        if let Err(e) = persist_audio_choice(&current) {
+                            messages.push(format!("No se pudo guardar el formato en config.toml: {}", e));
+                        }
+                    }
+                    KeyCode::Char(' ') if button_focused => {
+                        submit_input(
+                            &mut input,
+                            &queue,
+                            &mut messages,
+                            &mut playlist_prompt,
+                            settings.read().unwrap().allow_other_sites,
+                        );
+                    }
                     KeyCode::Char(c) => {
                         input.push(c);
                     }
@@ -448,16 +4972,15 @@ fn run_ui(download_tx: tokio_mpsc::Sender<String>, status_rx: Receiver<String>)
                     KeyCode::Tab => {
                         button_focused = !button_focused;
                     }
+                    // Enter envía tanto desde el input como con el botón enfocado
                     KeyCode::Enter => {
-                        let trimmed = input.trim();
-                        if !trimmed.is_empty() {
-                            // Enviar a worker usando blocking_send (estamos en hilo blocking)
-                            match download_tx.blocking_send(trimmed.to_string()) {
-                                Ok(()) => messages.push(format!("Queued: {}", trimmed)),
-                                Err(e) => messages.push(format!("Error encolar URL: {}", e)),
-                            }
-                            input.clear();
-                        }
+                        submit_input(
+                            &mut input,
+                            &queue,
+                            &mut messages,
+                            &mut playlist_prompt,
+                            settings.read().unwrap().allow_other_sites,
+                        );
                     }
                     _ => {}
                 }
@@ -468,43 +4991,788 @@ fn run_ui(download_tx: tokio_mpsc::Sender<String>, status_rx: Receiver<String>)
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    get_or_update_yt_dlp().await.unwrap();
+    let cli = Cli::parse();
+
+    let mut config = match Config::load(Path::new(CONFIG_FILE)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_STARTUP_ERROR);
+        }
+    };
+    if let Some(format_id) = cli.format_id.clone() {
+        config.audio_format_id = Some(format_id);
+        if let Err(e) = config.validate() {
+            eprintln!("--format-id: {}", e);
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+    }
+    if cli.no_reencode {
+        config.reencode_format_id = false;
+    }
+    if let Some(language) = cli.audio_lang.clone() {
+        config.audio_language = Some(language);
+        if let Err(e) = config.validate() {
+            eprintln!("--audio-lang: {}", e);
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+    }
+    if cli.any_bitrate {
+        config.min_audio_bitrate = None;
+    }
+    if cli.timings {
+        config.timings = true;
+    }
+    if cli.split_chapters {
+        config.split_chapters = true;
+    }
+    if let Some(genre) = cli.genre {
+        config.genre = Some(genre);
+        if let Err(e) = config.validate() {
+            eprintln!("--genre: {}", e);
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+    }
+    if cli.any_channel {
+        config.channel_allowlist.clear();
+    }
+    let cache_path = config.cache_metadata_on_disk.then(|| PathBuf::from(METADATA_CACHE_FILE));
+    let _ = METADATA_CACHE.set(Arc::new(MetadataCache::new(cache_path)));
+    if cli.refresh_metadata {
+        metadata_cache().clear();
+    }
+    if cli.speech {
+        config.apply_kind(ContentKind::Speech);
+    } else if cli.music {
+        config.apply_kind(ContentKind::Music);
+    }
+    if let Some(args) = cli.yt_dlp_args.clone() {
+        config.extra_yt_dlp_args = Some(args);
+        if let Err(e) = config.validate() {
+            eprintln!("--yt-dlp-args: {}", e);
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+    }
+
+    let wants_tui = cli.print_only.is_none()
+        && !cli.storage
+        && !cli.list_disks
+        && !cli.undo
+        && cli.export_manifest.is_none()
+        && !cli.scan_library
+        && cli.import_manifest.is_none()
+        && !cli.repair_library
+        && !cli.retag
+        && !cli.stdin
+        && cli.watch.is_none()
+        && !cli.headless
+        && cli.urls.is_empty();
+    // Con stdout redirigido (pipe o archivo) la TUI solo dejaría secuencias de control en la salida
+    let plain_output = wants_tui && !io::stdout().is_terminal();
+    let uses_tui = wants_tui && !plain_output;
+    // --quiet no tiene efecto con la interfaz
+    let quiet = cli.quiet && !uses_tui;
+    let log_level = if quiet {
+        LevelFilter::OFF
+    } else {
+        logging::effective_level(&config.log_level, cli.verbose).into()
+    };
+    if let Err(e) = logging::init(log_level, uses_tui) {
+        eprintln!("No se pudo iniciar el log: {:#}", e);
+        std::process::exit(EXIT_STARTUP_ERROR);
+    }
+
+    if let Some(args) = config.extra_yt_dlp_args.as_ref().filter(|_| !quiet) {
+        warn!(args, "argumentos extra de yt-dlp activos");
+        eprintln!("Aviso: se agregan argumentos extra a yt-dlp ({}). Es una opción avanzada y sin soporte.", args);
+    }
+
+    if let Some(url) = cli.print_only.as_deref() {
+        if let Err(e) = print_metadata(url, &config).await {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if cli.list_disks {
+        if let Err(e) = run_list_disks() {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if cli.undo {
+        if let Err(e) = run_undo().await {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = cli.export_manifest.as_deref() {
+        let dest_dir = cli.dest.clone().or_else(|| config.dest_dir.clone());
+        if let Err(e) = run_export_manifest(path, dest_dir.as_deref()) {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if cli.scan_library {
+        let dest_dir = cli.dest.clone().or_else(|| config.dest_dir.clone());
+        if let Err(e) = scan_library(&config, dest_dir.as_deref()).await {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = cli.import_manifest.as_deref() {
+        if let Err(e) = run_import_manifest(path, cli.dest.as_deref()) {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    // Antes de preparar los binarios, para no volver a bajar lo que se acaba de borrar
+    if cli.storage {
+        if let Err(e) = run_storage(cli.prune, cli.force).await {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if cli.retag {
+        if let Err(e) = retag_library(&config, cli.dry_run).await {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    if cli.repair_library {
+        if let Err(e) = repair_library(&config, cli.apply).await {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_TOTAL_FAILURE);
+        }
+        return Ok(());
+    }
+
+    let update_interval = Duration::from_secs(config.binaries_update_hours * 3600);
+    if let Err(e) = get_or_update_yt_dlp(update_interval).await {
+        error!(error = format!("{:#}", e), "no se pudieron preparar los binarios");
+        eprintln!("Error al preparar los binarios: {:#}", e);
+        eprintln!("Revisa tu conexión a internet o copia yt-dlp y ffmpeg a la carpeta './libs'.");
+        std::process::exit(EXIT_STARTUP_ERROR);
+    }
 
-    let (download_tx, mut download_rx) = tokio_mpsc::channel::<String>(32);
+    if !cli.no_repair {
+        let orphans = repair_staging_dir(STAGING_DIR).await;
+        if !orphans.is_empty() && !quiet {
+            println!("Reparación de output: {} archivos parciales o vacíos eliminados", orphans.len());
+        }
+        if let Some(temp_dir) = &config.download_temp_dir {
+            let orphans = repair_staging_dir(temp_dir).await;
+            if !orphans.is_empty() && !quiet {
+                println!("Reparación de {}: {} archivos parciales eliminados", temp_dir, orphans.len());
+            }
+        }
+    }
 
-    let (status_tx, status_rx) = mpsc::channel::<String>();
+    let staging_max_age = Duration::from_secs(config.staging_max_age_hours * 3600);
+    if config.staging_max_age_hours > 0 {
+        let (removed, reclaimed) = sweep_staging_dirs(STAGING_DIR, staging_max_age).await;
+        if removed > 0 && !quiet {
+            println!(
+                "Limpieza de output: {} archivos viejos eliminados ({} liberados)",
+                removed,
+                format_bytes(reclaimed)
+            );
+        }
+    }
 
     //let usb_path = r"F:\".to_string();
 
-    let mut output_path = String::new();
-    
-    println!("Ingrese la ruta de salida:");
-    std::io::stdin().read_line(&mut output_path)?;
-    let output_path = output_path.trim().to_string();
+    let output_path = match cli.dest.or_else(|| config.dest_dir.clone()) {
+        Some(dest) => dest,
+        // Con --stdin la pregunta se llevaría el primer enlace
+        None if cli.stdin => {
+            eprintln!("--stdin necesita --dest o dest_dir en config.toml");
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        }
+        None => {
+            let mut output_path = String::new();
+
+            println!("Ingrese la ruta de salida:");
+            std::io::stdin().read_line(&mut output_path)?;
+            output_path.trim().to_string()
+        }
+    };
+    config.dest_dir = Some(output_path.clone());
+    config.extra_dest_dirs.extend(cli.also_dest);
+
+    if !cli.no_check
+        && !run_self_check(&output_path, &config, quiet).await
+        && uses_tui
+        && !ask_yes_no("Algunas verificaciones fallaron. ¿Continuar de todos modos?")?
+    {
+        return Ok(());
+    }
+
+    if cli.stdin {
+        let summary = run_stdin(&output_path, &config, cli.playlist, cli.allow_duplicates, quiet).await;
+        std::process::exit(summary.exit_code());
+    }
+
+    if let Some(inbox) = &cli.watch {
+        let inbox = match (inbox.as_os_str().is_empty(), &config.watch_dir) {
+            (false, _) => inbox.clone(),
+            (true, Some(dir)) => PathBuf::from(dir),
+            (true, None) => {
+                eprintln!("--watch necesita una carpeta o watch_dir en config.toml");
+                std::process::exit(EXIT_BAD_ARGUMENTS);
+            }
+        };
+        let summary = run_watch(&inbox, &output_path, &config, cli.playlist, cli.allow_duplicates, quiet).await;
+        std::process::exit(summary.exit_code());
+    }
+
+    if !cli.urls.is_empty() {
+        let summary = run_headless(&cli.urls, &output_path, &config, cli.playlist, quiet).await;
+        std::process::exit(summary.exit_code());
+    }
+
+    let imported = match &cli.import_bookmarks {
+        Some(path) => match bookmarks::load(path) {
+            Ok(urls) => urls
+                .iter()
+                .filter_map(|parsed| resolve_url(parsed, cli.playlist))
+                .collect(),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(EXIT_BAD_ARGUMENTS);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let clipboard_url = if cli.from_clipboard {
+        match read_clipboard_url(cli.playlist, config.allow_other_sites) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if cli.headless {
+        let Some(url) = clipboard_url else {
+            eprintln!("No hay enlace en el portapapeles: --headless necesita un enlace válido copiado");
+            std::process::exit(EXIT_BAD_ARGUMENTS);
+        };
+        let summary = run_headless(&[url], &output_path, &config, cli.playlist, quiet).await;
+        std::process::exit(summary.exit_code());
+    }
+
+    // Sin terminal se descargan los enlaces importados y después se leen enlaces por stdin
+    if plain_output {
+        if !quiet {
+            eprintln!("stdout no es una terminal: se usa el modo sin interfaz (un enlace por línea en stdin)");
+        }
+        let urls: Vec<String> = imported.into_iter().chain(clipboard_url).collect();
+        let mut summary = run_headless(&urls, &output_path, &config, cli.playlist, quiet).await;
+        summary.merge(run_stdin(&output_path, &config, cli.playlist, cli.allow_duplicates, quiet).await);
+        std::process::exit(summary.exit_code());
+    }
+
+    let mut restored = queue::load_saved(Path::new(QUEUE_FILE));
+    if !restored.is_empty()
+        && !ask_yes_no(&format!("Hay {} descargas pendientes de la sesión anterior. ¿Restaurarlas?", restored.len()))?
+    {
+        restored.clear();
+    }
+
+    // Una descarga interrumpida se repite desde cero, sin sus archivos parciales
+    if restored.iter().any(|item| item.started) {
+        let removed = clear_staging_dirs(STAGING_DIR).await;
+        println!("Archivos parciales eliminados: {}", removed);
+        for item in restored.iter_mut() {
+            item.started = false;
+        }
+    }
+
+    let restored_count = restored.len();
+    let queue = Arc::new(DownloadQueue::new(Some(PathBuf::from(QUEUE_FILE)), restored));
+
+    let (status_tx, status_rx) = status::channel();
+
+    if restored_count > 0 {
+        let _ = status_tx.send(format!("Restauradas {} descargas pendientes", restored_count));
+    }
+
+    match get_disk_info() {
+        Ok(disks) => {
+            for disk in disks {
+                info!(name = disk.name, total = disk.total, free = disk.free, "unidad extraíble");
+                let _ = status_tx.send(disk.summary());
+            }
+        }
+        Err(e) => debug!(error = %e, "sin unidades extraíbles"),
+    }
+
+    if cli.import_bookmarks.is_some() {
+        let _ = status_tx.send(format!("Importados {} enlaces de YouTube desde los marcadores", imported.len()));
+    }
+    let imported_ids = queue.push_batch(imported.clone(), None);
+    for (id, url) in imported_ids.into_iter().zip(imported) {
+        let ahead = queue.ahead_of(id).unwrap_or(0);
+        let _ = status_tx.send(format!("Queued ({} ahead): {}", ahead, url));
+    }
+
+    if let Some(url) = clipboard_url {
+        let id = queue.push(url.clone(), None);
+        let ahead = queue.ahead_of(id).unwrap_or(0);
+        let _ = status_tx.send(format!("Queued ({} ahead) desde el portapapeles: {}", ahead, url));
+    }
+
+    let active_downloads = Arc::new(AtomicUsize::new(0));
+
+    if config.staging_max_age_hours > 0 {
+        tokio::spawn(run_staging_cleanup(STAGING_DIR, staging_max_age, active_downloads.clone(), status_tx.clone()));
+    }
+
+    // La pantalla de ajustes modifica esta copia; el worker la lee en cada descarga
+    let settings = Arc::new(RwLock::new(config));
 
     let worker_handle = tokio::spawn({
         let status_tx = status_tx.clone();
-        let usb_path = output_path.clone();
+        let settings = settings.clone();
+        let queue = queue.clone();
+        let active_downloads = active_downloads.clone();
         async move {
-            while let Some(url) = download_rx.recv().await {
-                let _ = status_tx.send(format!("Descargando: {}", url));
-
-                match download(&url, &usb_path, &status_tx).await {
-                    Ok(()) => {
-                        let _ = status_tx.send(format!("Done: {}", url));
-                    }
-                    Err(e) => {
-                        let _ = status_tx.send(format!("Error: {} -> {}", url, e));
-                    }
-                }
-            }
-            let _ = status_tx.send("Worker: channel closed, exiting worker.".to_string());
+            run_worker(queue, settings, status_tx.clone(), active_downloads).await;
+            let _ = status_tx.send("Worker: queue closed, exiting worker.".to_string());
         }
     });
 
-    let _ui_result = tokio::task::spawn_blocking(move || run_ui(download_tx, status_rx)).await??;
+    tokio::spawn(run_metadata_prefetch(queue.clone(), settings.clone()));
+
+    let versions = BinaryVersions::detect(&status_tx).await;
+    info!(versions = versions.label(), "binarios");
 
+    let _ui_result = tokio::task::spawn_blocking({
+        let queue = queue.clone();
+        move || run_ui(queue, status_tx, status_rx, settings, versions)
+    }).await??;
+
+    // Lo que quede sin empezar se guarda para la próxima sesión
+    queue.close();
     let _ = worker_handle.await;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_paths_relative_to_the_destination() {
+        let roots = vec![PathBuf::from("/media/usb/Musica"), PathBuf::from("/media/usb")];
+        assert_eq!(
+            relative_paths("Archivo movido a: \"/media/usb/Musica/Artista/Tema.mp3\"", &roots),
+            "Archivo movido a: \"Artista/Tema.mp3\""
+        );
+        assert_eq!(relative_paths("Copiado a: /media/usb/Otro/Tema.mp3", &roots), "Copiado a: Otro/Tema.mp3");
+
+        let windows = vec![PathBuf::from(r"E:\Musica")];
+        assert_eq!(relative_paths(r#"Guardado: "E:\\Musica\\Artista\\Tema.mp3""#, &windows), r#"Guardado: "Artista\\Tema.mp3""#);
+    }
+
+    #[test]
+    fn detects_paths_that_leave_the_destination() {
+        let root = Path::new("musica");
+        assert!(!path_escapes(root, &root.join("Artista").join("Álbum")));
+        assert!(path_escapes(root, &root.join("Artista").join("..").join("..").join("sistema")));
+        assert!(path_escapes(root, Path::new("/etc/musica")));
+    }
+
+    #[test]
+    fn sets_genre_by_session_or_destination_folder() {
+        let mut config = Config {
+            genre_folders: vec![
+                config::GenreFolder { folder: "Rock".to_string(), genre: "Rock".to_string() },
+                config::GenreFolder { folder: "Rock/Clasico".to_string(), genre: "Classic Rock".to_string() },
+            ],
+            ..Config::default()
+        };
+        assert_eq!(config.genre_for(Path::new("/musica/Rock")), Some("Rock"));
+        assert_eq!(config.genre_for(Path::new("/musica/Rock/Clasico")), Some("Classic Rock"));
+        assert_eq!(config.genre_for(Path::new("/musica/Jazz")), None);
+
+        config.genre = Some("AC\\DC".to_string());
+        assert_eq!(config.genre_for(Path::new("/musica/Rock")), Some("AC\\DC"));
+        assert_eq!(genre_args(config.genre_for(Path::new("/musica")))[5], "AC\\\\DC");
+        assert!(genre_args(None).is_empty());
+
+        // Un video cualquiera (sin álbum) también se etiqueta
+        let args = metadata_args(false, Some("Rock"), &config);
+        assert_eq!(args[0], "--embed-metadata");
+        assert!(args.windows(2).any(|pair| pair == ["(?s)^.+$", "Rock"]));
+        config.url_comment = false;
+        assert!(metadata_args(false, None, &config).is_empty());
+    }
+
+    #[test]
+    fn writes_the_source_url_as_comment() {
+        let mut config = Config::default();
+        assert_eq!(comment_args(&config), vec!["--parse-metadata", "%(webpage_url)s:%(meta_comment)s"]);
+
+        config.url_comment_upload_date = true;
+        assert_eq!(comment_args(&config)[1], "%(webpage_url)s (%(upload_date>%Y-%m-%d|sin fecha)s):%(meta_comment)s");
+
+        config.url_comment = false;
+        assert!(comment_args(&config).iter().all(|arg| !arg.contains("webpage_url")));
+
+        // Un video cualquiera, sin álbum ni género, lleva el enlace en el comentario
+        let args = metadata_args(false, None, &Config::default());
+        assert_eq!(args, vec!["--embed-metadata", "--parse-metadata", "%(webpage_url)s:%(meta_comment)s"]);
+    }
+
+    #[test]
+    fn expects_the_extension_yt_dlp_leaves() {
+        let mut config = Config { audio_format: "vorbis".to_string(), ..Config::default() };
+        assert_eq!(downloaded_extension(&config), Some("ogg"));
+
+        config.remux_container = Some("mka".to_string());
+        assert_eq!(downloaded_extension(&config), None);
+
+        assert!(is_partial_file_name("tema.webm.part") && is_partial_file_name("tema.f251.webm.part-Frag3"));
+        assert!(!is_partial_file_name("tema.opus"));
+    }
+
+    #[test]
+    fn reads_urls_from_inbox_files() {
+        let shortcut = "[InternetShortcut]\r\nURL=https://www.youtube.com/watch?v=abc\r\nIconIndex=0\r\n";
+        assert_eq!(urls_in_inbox_file(shortcut), vec!["https://www.youtube.com/watch?v=abc"]);
+
+        let list = "https://youtu.be/a  https://youtu.be/b\nnota sin enlace\n\nhttps://youtu.be/c\n";
+        assert_eq!(urls_in_inbox_file(list), vec!["https://youtu.be/a", "https://youtu.be/b", "https://youtu.be/c"]);
+    }
+
+    #[test]
+    fn reads_chapter_number_and_title_from_file_name() {
+        assert_eq!(parse_chapter_file("003 - Intro - En vivo.mp3"), Some((3, "Intro - En vivo".to_string())));
+        assert_eq!(parse_chapter_file("Mix completo.mp3"), None);
+    }
+
+    #[test]
+    fn reads_playlist_pages_with_optional_total() {
+        let (entries, total) = parse_playlist_page("250\thttps://youtu.be/a\n250\thttps://youtu.be/b\n\n");
+        assert_eq!(entries, vec!["https://youtu.be/a", "https://youtu.be/b"]);
+        assert_eq!(total, Some(250));
+
+        let (entries, total) = parse_playlist_page("\thttps://youtu.be/a\nhttps://youtu.be/b\n");
+        assert_eq!(entries, vec!["https://youtu.be/a", "https://youtu.be/b"]);
+        assert_eq!(total, None);
+    }
+
+    #[test]
+    fn sanitize_empty_input_falls_back() {
+        assert_eq!(sanitize_filename(""), FALLBACK_FILE_NAME);
+        assert_eq!(sanitize_filename("   "), FALLBACK_FILE_NAME);
+    }
+
+    #[test]
+    fn sanitize_all_invalid_input_falls_back() {
+        assert_eq!(sanitize_filename(r#"<>:"/\|?*"#), FALLBACK_FILE_NAME);
+        assert_eq!(sanitize_filename("..."), FALLBACK_FILE_NAME);
+        assert_eq!(sanitize_filename("?? ..."), FALLBACK_FILE_NAME);
+    }
+
+    #[test]
+    fn sanitize_uses_custom_fallback() {
+        assert_eq!(sanitize_filename_or("...", UNKNOWN_ARTIST), UNKNOWN_ARTIST);
+        assert_eq!(sanitize_filename_or("Queen", UNKNOWN_ARTIST), "Queen");
+    }
+
+    #[test]
+    fn sanitize_collapses_invalid_runs() {
+        assert_eq!(sanitize_filename("AC/DC"), "AC_DC");
+        assert_eq!(sanitize_filename("What?<>Now"), "What_Now");
+    }
+
+    #[test]
+    fn sanitize_trims_dots_and_spaces() {
+        assert_eq!(sanitize_filename("  Song name. . "), "Song name");
+        assert_eq!(sanitize_filename(".hidden"), "hidden");
+    }
+
+    #[test]
+    fn sanitize_keeps_unicode() {
+        assert_eq!(sanitize_filename("Canción ñandú 東京"), "Canción ñandú 東京");
+    }
+
+    #[test]
+    fn sanitize_truncates_to_32_chars() {
+        let long = "a".repeat(40);
+        assert_eq!(sanitize_filename(&long), "a".repeat(32));
+
+        let unicode = "é".repeat(40);
+        assert_eq!(sanitize_filename(&unicode).chars().count(), 32);
+
+        // El espacio que queda tras truncar también se recorta
+        let spaced = format!("{} tail", "a".repeat(31));
+        assert_eq!(sanitize_filename(&spaced), "a".repeat(31));
+    }
+
+    fn track(title: &str, author_name: &str) -> VideoMetadata {
+        VideoMetadata {
+            title: title.to_string(),
+            author_name: author_name.to_string(),
+            album: None,
+            track_number: None,
+        }
+    }
+
+    #[test]
+    fn naming_schemes() {
+        let dest = Path::new("musica");
+        let plain = track("Song", "Artist");
+        let named = track("Artist - Song", "Artist");
+
+        let cases = [
+            (NamingScheme::Legacy, &plain, "musica/Artist/Artist-Song.mp3"),
+            (NamingScheme::Legacy, &named, "musica/Artist/Artist - Song.mp3"),
+            (NamingScheme::ArtistTitle, &plain, "musica/Artist-Song.mp3"),
+            (NamingScheme::ArtistTitle, &named, "musica/Artist-Artist - Song.mp3"),
+            (NamingScheme::TitleOnly, &plain, "musica/Song.mp3"),
+            (NamingScheme::ArtistFolder, &plain, "musica/Artist/Song.mp3"),
+        ];
+        for (naming, metadata, expected) in cases {
+            let layout = LibraryLayout { naming, shards: ShardScheme::None, keep_original_name: false, artist_prefix: ArtistPrefix::Auto, safe_paths: true };
+            assert_eq!(expected_path(dest, metadata, layout, "mp3"), Path::new(expected), "{:?}", naming);
+        }
+
+        let album_track = VideoMetadata {
+            album: Some("Album".to_string()),
+            track_number: Some(3),
+            ..track("Song", "Artist")
+        };
+        let layout = LibraryLayout { naming: NamingScheme::TitleOnly, shards: ShardScheme::None, keep_original_name: false, artist_prefix: ArtistPrefix::Auto, safe_paths: true };
+        assert_eq!(
+            expected_path(dest, &album_track, layout, "mp3"),
+            Path::new("musica/Artist/Album/03 - Song.mp3")
+        );
+    }
+
+    #[test]
+    fn artist_prefix_only_dropped_when_title_starts_with_artist() {
+        let dest = Path::new("musica");
+        let named = track("Artist - Song", "Artist");
+        let mid_title = track("Song by SomeoneElse", "SomeoneElse");
+
+        let cases = [
+            (ArtistPrefix::Auto, &named, "musica/Artist/Artist - Song.mp3"),
+            (ArtistPrefix::Auto, &mid_title, "musica/SomeoneElse/SomeoneElse-Song by SomeoneElse.mp3"),
+            (ArtistPrefix::Always, &named, "musica/Artist/Artist-Artist - Song.mp3"),
+            (ArtistPrefix::Never, &mid_title, "musica/SomeoneElse/Song by SomeoneElse.mp3"),
+        ];
+        for (artist_prefix, metadata, expected) in cases {
+            let layout = LibraryLayout {
+                naming: NamingScheme::Legacy,
+                shards: ShardScheme::None,
+                keep_original_name: false,
+                artist_prefix,
+                safe_paths: true,
+            };
+            assert_eq!(expected_path(dest, metadata, layout, "mp3"), Path::new(expected), "{:?}", artist_prefix);
+        }
+    }
+
+    #[test]
+    fn rebuilds_names_from_library_paths() {
+        let names = |path: &str| names_from_path(Path::new(path));
+
+        assert_eq!(
+            names("Artist/Album/03 - Song.mp3"),
+            ("Artist".to_string(), "Song".to_string(), Some("Album".to_string()), Some(3))
+        );
+        assert_eq!(names("Artist/Artist-Song.mp3"), ("Artist".to_string(), "Song".to_string(), None, None));
+        assert_eq!(names("Artist/Song.flac"), ("Artist".to_string(), "Song".to_string(), None, None));
+        assert_eq!(names("Artist-Song.mp3"), ("Artist".to_string(), "Song".to_string(), None, None));
+        assert_eq!(names("Song.mp3"), (UNKNOWN_ARTIST.to_string(), "Song".to_string(), None, None));
+    }
+
+    #[test]
+    fn shards_by_first_letter() {
+        assert_eq!(shard_name("artist"), "A");
+        assert_eq!(shard_name("Ángeles Azules"), "A");
+        assert_eq!(shard_name("Ñengo Flow"), "N");
+        assert_eq!(shard_name("2Pac"), "#");
+        assert_eq!(shard_name("!!!"), "#");
+        assert_eq!(shard_name("宇多田ヒカル"), "Otros");
+
+        let layout = LibraryLayout { naming: NamingScheme::ArtistFolder, shards: ShardScheme::FirstLetter, keep_original_name: false, artist_prefix: ArtistPrefix::Auto, safe_paths: true };
+        assert_eq!(
+            expected_path(Path::new("musica"), &track("Song", "Artist"), layout, "mp3"),
+            Path::new("musica/A/Artist/Song.mp3")
+        );
+    }
+
+    #[test]
+    fn duration_suffix_is_a_valid_file_name() {
+        assert_eq!(duration_suffix(222), " [03.42]");
+        assert_eq!(duration_suffix(3723), " [01.02.03]");
+    }
+
+    #[test]
+    fn exit_code_by_outcome() {
+        let summary = |succeeded, failed, invalid| RunSummary { succeeded, failed, invalid };
+        assert_eq!(summary(3, 0, 0).exit_code(), 0);
+        assert_eq!(summary(0, 0, 0).exit_code(), 0);
+        assert_eq!(summary(2, 1, 0).exit_code(), EXIT_PARTIAL_FAILURE);
+        assert_eq!(summary(2, 0, 1).exit_code(), EXIT_PARTIAL_FAILURE);
+        assert_eq!(summary(0, 0, 2).exit_code(), EXIT_BAD_ARGUMENTS);
+        assert_eq!(summary(0, 2, 1).exit_code(), EXIT_TOTAL_FAILURE);
+    }
+
+    #[test]
+    fn format_bytes_boundaries() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(1024 * 1024 - 1), "1024.0 KiB");
+        assert_eq!(format_bytes(1024 * 1024), "1.0 MiB");
+        assert_eq!(format_bytes(32 * 1024 * 1024 * 1024), "32.0 GiB");
+        assert_eq!(format_bytes(3 * 1024u64.pow(4)), "3.0 TiB");
+        // Más allá de TiB se sigue contando en TiB
+        assert_eq!(format_bytes(2048 * 1024u64.pow(4)), "2048.0 TiB");
+    }
+
+    #[test]
+    fn recognizes_orphaned_staging_files() {
+        assert_eq!(job_dir_pid("a-b_c-DEF-4321-7"), Some(4321));
+        assert_eq!(job_dir_pid("cache"), None);
+
+        assert!(is_orphan_file(Path::new("tema.webm.part"), 10));
+        assert!(is_orphan_file(Path::new("tema.f251.webm.part-Frag3"), 10));
+        assert!(is_orphan_file(Path::new("tema.mp3"), 0));
+        assert!(!is_orphan_file(Path::new("tema.mp3"), 10));
+    }
+
+    #[test]
+    fn long_non_music_videos_look_like_speech() {
+        let info = |json: &str| serde_json::from_str::<VideoInfo>(json).unwrap();
+
+        assert!(info(r#"{"title": "Episodio 12", "duration": 3600, "categories": ["People & Blogs"]}"#).looks_like_speech(20));
+        assert!(!info(r#"{"title": "Episodio 12", "duration": 600, "categories": ["Education"]}"#).looks_like_speech(20));
+        assert!(!info(r#"{"title": "DJ set", "duration": 3600, "categories": ["Music"]}"#).looks_like_speech(20));
+        assert!(!info(r#"{"title": "Suite", "duration": 1800, "track": "Suite"}"#).looks_like_speech(20));
+    }
+
+    #[test]
+    fn lists_dubbed_audio_tracks_original_first() {
+        let info: VideoInfo = serde_json::from_str(
+            r#"{"title": "Tema", "formats": [
+                {"format_id": "251-0", "acodec": "opus", "language": "en", "language_preference": -1},
+                {"format_id": "251-1", "acodec": "opus", "language": "ja", "language_preference": 10},
+                {"format_id": "140-0", "acodec": "mp4a.40.2", "language": "en", "language_preference": -1},
+                {"format_id": "137", "acodec": "none", "language": "fr"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(info.audio_languages_label(), "ja (original), en");
+
+        let config = Config { audio_language: Some("ja".to_string()), ..Config::default() };
+        assert_eq!(config.audio_format_selector().as_deref(), Some("bestaudio[language^=ja]/bestaudio/best"));
+    }
+
+    #[test]
+    fn format_id_must_exist_and_have_audio() {
+        let info: VideoInfo = serde_json::from_str(
+            r#"{"title": "Tema", "formats": [
+                {"format_id": "251", "acodec": "opus", "abr": 130.5},
+                {"format_id": "140", "acodec": "mp4a.40.2", "abr": 129.5},
+                {"format_id": "137", "acodec": "none", "vcodec": "avc1"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert!(check_format_id(&info, "251").is_ok());
+        assert!(check_format_id(&info, "137").unwrap_err().to_string().contains("no tiene audio"));
+        let missing = check_format_id(&info, "999").unwrap_err().to_string();
+        assert!(missing.contains("251, 140"), "{}", missing);
+        assert_eq!(info.best_audio_bitrate(), Some(130.5));
+    }
+
+    #[tokio::test]
+    async fn worker_keeps_going_after_a_panicking_download() {
+        let queue = Arc::new(DownloadQueue::new(None, Vec::new()));
+        for url in ["primero", "roto", "tercero"] {
+            queue.push(url.to_string(), Some("destino".to_string()));
+        }
+        queue.finish();
+
+        let (tx, rx) = status::channel();
+        let summary = run_worker_with(
+            queue.clone(),
+            Arc::new(RwLock::new(Config::default())),
+            tx,
+            Arc::new(AtomicUsize::new(0)),
+            |url, _, _, _| async move {
+                if url == "roto" {
+                    panic!("descarga rota");
+                }
+                true
+            },
+        )
+        .await;
+
+        assert_eq!((summary.succeeded, summary.failed), (2, 1));
+        assert!(queue.snapshot().is_empty());
+        assert!(rx
+            .try_iter()
+            .any(|event| matches!(event, StatusEvent::Error(message) if message.contains("descarga rota"))));
+    }
+
+    #[tokio::test]
+    async fn reads_metadata_from_a_custom_oembed_server() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Servidor HTTP mínimo que responde una sola vez con un JSON fijo
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let read = socket.read(&mut request).await.unwrap();
+            let body = r#"{"title": "Tema", "author_name": "Artista", "provider_name": "YouTube"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..read]).to_string()
+        });
+
+        let url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+        let metadata = fetch_oembed(&reqwest::Client::new(), &base_url, url).await.unwrap();
+        assert_eq!(metadata.title, "Tema");
+        assert_eq!(metadata.author_name, "Artista");
+        assert_eq!(metadata.album, None);
+
+        let request = server.await.unwrap();
+        let expected = "GET /oembed?url=https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3DdQw4w9WgXcQ&format=json ";
+        assert!(request.starts_with(expected), "{}", request);
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::HistoryEntry;
+
+// Un tema de la biblioteca; la ruta es relativa a la carpeta destino para que sirva aunque cambie la letra de la unidad
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub url: String,
+    pub artist: String,
+    pub title: String,
+    pub path: PathBuf,
+    pub format: String,
+    pub bytes: u64,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub track_number: Option<u32>,
+    // Segundos desde UNIX_EPOCH
+    pub downloaded_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    // Carpeta destino al exportar; al importar se usa si no se indica otra
+    pub dest_root: PathBuf,
+    pub entries: Vec<ManifestEntry>,
+}
+
+// Solo lo que sigue en `dest_root`; las descargas fallidas y los archivos borrados no se exportan
+pub fn from_history(entries: &[HistoryEntry], dest_root: &Path) -> Manifest {
+    let entries = entries
+        .iter()
+        .filter(|entry| entry.error.is_none() && entry.dest_root == dest_root && entry.path.exists())
+        .map(|entry| ManifestEntry {
+            url: entry.url.clone(),
+            artist: entry.artist.clone(),
+            title: entry.title.clone(),
+            path: entry.path.strip_prefix(dest_root).unwrap_or(&entry.path).to_path_buf(),
+            format: entry
+                .path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            bytes: entry.bytes,
+            album: entry.album.clone(),
+            track_number: entry.track_number,
+            downloaded_at: entry.downloaded_at,
+        })
+        .collect();
+
+    Manifest { dest_root: dest_root.to_path_buf(), entries }
+}
+
+// Entradas de historial para `dest_root`, sin las que ya están en `existing`
+pub fn to_history(manifest: &Manifest, dest_root: &Path, existing: &[HistoryEntry]) -> Vec<HistoryEntry> {
+    manifest
+        .entries
+        .iter()
+        .map(|entry| HistoryEntry {
+            url: entry.url.clone(),
+            path: dest_root.join(&entry.path),
+            dest_root: dest_root.to_path_buf(),
+            artist: entry.artist.clone(),
+            title: entry.title.clone(),
+            bytes: entry.bytes,
+            album: entry.album.clone(),
+            track_number: entry.track_number,
+            error: None,
+            fingerprint: None,
+            downloaded_at: entry.downloaded_at,
+        })
+        .filter(|entry| !existing.iter().any(|known| known.path == entry.path))
+        .collect()
+}
+
+pub fn save(path: &Path, manifest: &Manifest) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+
+    std::fs::write(path, json).map_err(|e| format!("No se pudo guardar el manifiesto {:?}: {}", path, e))
+}
+
+pub fn load(path: &Path) -> Result<Manifest, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("No se pudo leer el manifiesto {:?}: {}", path, e))?;
+
+    serde_json::from_str(&raw).map_err(|e| format!("Manifiesto inválido en {:?}: {}", path, e))
+}
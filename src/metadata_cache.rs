@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub const METADATA_CACHE_FILE: &str = "metadata_cache.json";
+
+// Metadata ya pedida, por id de video (ver youtube_url::queue_key); si tiene ruta se guarda en disco en cada cambio
+pub struct MetadataCache<T> {
+    entries: Mutex<HashMap<String, T>>,
+    path: Option<PathBuf>,
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> MetadataCache<T> {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let entries = path.as_deref().map(load_saved).unwrap_or_default();
+        MetadataCache { entries: Mutex::new(entries), path }
+    }
+
+    pub fn get(&self, key: &str) -> Option<T> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: String, value: T) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, value);
+        self.persist(&entries);
+    }
+
+    // Devuelve cuántas entradas había
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.clear();
+        self.persist(&entries);
+        count
+    }
+
+    fn persist(&self, entries: &HashMap<String, T>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Ok(json) = serde_json::to_string_pretty(entries) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+}
+
+// Un archivo que falta o no se puede leer es una caché vacía
+fn load_saved<T: DeserializeOwned>(path: &Path) -> HashMap<String, T> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_entries_until_cleared() {
+        let cache = MetadataCache::new(None);
+        cache.insert("video:dQw4w9WgXcQ".to_string(), "Rick Astley - Never Gonna Give You Up".to_string());
+
+        assert_eq!(cache.get("video:dQw4w9WgXcQ").as_deref(), Some("Rick Astley - Never Gonna Give You Up"));
+        assert_eq!(cache.get("video:otro"), None);
+        assert_eq!(cache.clear(), 1);
+        assert_eq!(cache.get("video:dQw4w9WgXcQ"), None);
+    }
+}
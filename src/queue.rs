@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::config::ContentKind;
+use crate::status::BatchProgress;
+
+pub const QUEUE_FILE: &str = "queue.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueItem {
+    pub id: u64,
+    pub url: String,
+    // Si la descarga ya había empezado (para limpiar parciales al restaurar)
+    #[serde(default)]
+    pub started: bool,
+    // Carpeta destino solo para este elemento; None = la global
+    #[serde(default)]
+    pub dest: Option<String>,
+    // Lote al que pertenece; el avance se calcula con los que siguen en la cola
+    #[serde(default)]
+    pub batch: Option<Batch>,
+    // "Artista - Título" buscado de antemano para el panel de la cola
+    #[serde(default)]
+    pub title: Option<String>,
+    // Se descarga aunque el audio no llegue a min_audio_bitrate
+    #[serde(default)]
+    pub any_bitrate: bool,
+    // Música o voz; None = según config.toml (detect_speech)
+    #[serde(default)]
+    pub kind: Option<ContentKind>,
+}
+
+// Ciclo de vida de un elemento: Queued -> FetchingMetadata -> Downloading -> Moving -> Done | Error | Cancelled.
+// En una playlist se repite Downloading -> Moving por cada tema
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ItemState {
+    #[default]
+    Queued,
+    FetchingMetadata,
+    // Porcentaje informado por yt-dlp; None hasta el primer dato
+    Downloading { pct: Option<f32> },
+    Moving,
+    Done,
+    Error,
+    Cancelled,
+}
+
+impl ItemState {
+    pub fn is_final(&self) -> bool {
+        matches!(self, ItemState::Done | ItemState::Error | ItemState::Cancelled)
+    }
+
+    pub fn can_become(&self, next: &ItemState) -> bool {
+        use ItemState::*;
+        match (self, next) {
+            (current, _) if current.is_final() => false,
+            (_, Done | Error | Cancelled) => true,
+            (Queued, FetchingMetadata) => true,
+            (FetchingMetadata | Downloading { .. } | Moving, Downloading { .. }) => true,
+            (Downloading { .. }, Moving) => true,
+            _ => false,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ItemState::Queued => "en cola".to_string(),
+            ItemState::FetchingMetadata => "metadata".to_string(),
+            ItemState::Downloading { pct: Some(pct) } => format!("descargando {:.0}%", pct),
+            ItemState::Downloading { pct: None } => "descargando".to_string(),
+            ItemState::Moving => "moviendo".to_string(),
+            ItemState::Done => "listo".to_string(),
+            ItemState::Error => "error".to_string(),
+            ItemState::Cancelled => "cancelado".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Batch {
+    pub id: u64,
+    pub total: usize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    items: Vec<QueueItem>,
+    next_id: u64,
+    closed: bool,
+    // En pausa no se entregan elementos nuevos; los que ya empezaron siguen
+    paused: bool,
+    // No llegan más elementos: el worker vacía la cola y termina
+    finished: bool,
+    // Fallidos por lote en esta sesión
+    failed: HashMap<u64, usize>,
+    // Aviso de cancelación de cada descarga en curso
+    cancels: HashMap<u64, Arc<Notify>>,
+}
+
+// Cola compartida entre la UI y el worker; si tiene ruta se guarda en disco en cada cambio
+pub struct DownloadQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+    path: Option<PathBuf>,
+}
+
+impl DownloadQueue {
+    pub fn new(path: Option<PathBuf>, items: Vec<QueueItem>) -> Self {
+        let next_id = items.iter().map(|item| item.id + 1).max().unwrap_or(0);
+        let queue = DownloadQueue {
+            state: Mutex::new(QueueState { items, next_id, ..Default::default() }),
+            notify: Notify::new(),
+            path,
+        };
+        queue.persist(&queue.state.lock().unwrap());
+        queue
+    }
+
+    pub fn push(&self, url: String, dest: Option<String>) -> u64 {
+        self.push_item(url, dest, false)
+    }
+
+    // Para volver a encolar un tema omitido por bitrate bajo
+    pub fn push_any_bitrate(&self, url: String, dest: Option<String>) -> u64 {
+        self.push_item(url, dest, true)
+    }
+
+    fn push_item(&self, url: String, dest: Option<String>, any_bitrate: bool) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.items.push(QueueItem {
+            id,
+            url,
+            started: false,
+            dest,
+            batch: None,
+            title: None,
+            any_bitrate,
+            kind: None,
+        });
+        self.persist(&state);
+        drop(state);
+
+        self.notify.notify_one();
+        id
+    }
+
+    // Encola varios enlaces como un lote; devuelve los ids en el mismo orden
+    pub fn push_batch(&self, urls: Vec<String>, dest: Option<String>) -> Vec<u64> {
+        let mut state = self.state.lock().unwrap();
+        let batch = Batch { id: state.next_id, total: urls.len() };
+        let mut ids = Vec::new();
+        for url in urls {
+            let id = state.next_id;
+            state.next_id += 1;
+            state.items.push(QueueItem {
+                id,
+                url,
+                started: false,
+                dest: dest.clone(),
+                batch: Some(batch),
+                title: None,
+                any_bitrate: false,
+                kind: None,
+            });
+            ids.push(id);
+        }
+        self.persist(&state);
+        drop(state);
+
+        self.notify.notify_waiters();
+        ids
+    }
+
+    // Espera el siguiente elemento sin empezar que cumpla `ready` (los demás esperan su turno); None cuando la cola se cerró.
+    // Si `ready` cambia por algo externo a la cola, hay que llamar a `wake`
+    pub async fn next(&self, ready: impl Fn(&QueueItem) -> bool) -> Option<QueueItem> {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                // En pausa se espera igual que con la cola vacía
+                if !state.paused {
+                    if let Some(item) = state.items.iter_mut().find(|item| !item.started && ready(item)) {
+                        item.started = true;
+                        let item = item.clone();
+                        self.persist(&state);
+                        return Some(item);
+                    }
+                    if state.finished && state.items.iter().all(|item| item.started) {
+                        return None;
+                    }
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    // Cuántos elementos hay delante (en curso o esperando); None si ya salió de la cola
+    pub fn ahead_of(&self, id: u64) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state.items.iter().position(|item| item.id == id)
+    }
+
+    pub fn cancel_signal(&self, id: u64) -> Arc<Notify> {
+        let mut state = self.state.lock().unwrap();
+        state.cancels.entry(id).or_default().clone()
+    }
+
+    // Un elemento sin empezar se saca de la cola; uno en curso recibe el aviso y el worker lo termina
+    pub fn cancel(&self, id: u64) -> Option<QueueItem> {
+        let mut state = self.state.lock().unwrap();
+        let item = state.items.iter().find(|item| item.id == id)?.clone();
+
+        if item.started {
+            // Si el worker todavía no pidió el aviso, el permiso queda guardado para cuando lo haga
+            state.cancels.entry(id).or_default().notify_one();
+        } else {
+            state.items.retain(|item| item.id != id);
+            self.persist(&state);
+        }
+        Some(item)
+    }
+
+    pub fn set_title(&self, id: u64, title: String) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(item) = state.items.iter_mut().find(|item| item.id == id) {
+            item.title = Some(title);
+            self.persist(&state);
+        }
+    }
+
+    // Solo antes de empezar; devuelve si se pudo cambiar
+    pub fn set_kind(&self, id: u64, kind: Option<ContentKind>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(item) = state.items.iter_mut().find(|item| item.id == id && !item.started) else {
+            return false;
+        };
+        item.kind = kind;
+        self.persist(&state);
+        true
+    }
+
+    // Copia del estado para mostrarlo en la interfaz
+    pub fn snapshot(&self) -> Vec<QueueItem> {
+        self.state.lock().unwrap().items.clone()
+    }
+
+    // Saca el elemento de la cola; si era parte de un lote devuelve el avance del lote
+    pub fn complete(&self, id: u64, ok: bool) -> Option<BatchProgress> {
+        let mut state = self.state.lock().unwrap();
+        let batch = state.items.iter().find(|item| item.id == id).and_then(|item| item.batch);
+        state.items.retain(|item| item.id != id);
+        state.cancels.remove(&id);
+        self.persist(&state);
+
+        let batch = batch?;
+        if !ok {
+            *state.failed.entry(batch.id).or_default() += 1;
+        }
+        let remaining = state
+            .items
+            .iter()
+            .filter(|item| item.batch.map(|b| b.id) == Some(batch.id))
+            .count();
+        let failed = if remaining == 0 {
+            state.failed.remove(&batch.id)
+        } else {
+            state.failed.get(&batch.id).copied()
+        };
+
+        Some(BatchProgress {
+            key: format!("lote-{}", batch.id),
+            total: batch.total,
+            done: batch.total.saturating_sub(remaining),
+            failed: failed.unwrap_or(0),
+            skipped: 0,
+        })
+    }
+
+    pub fn wake(&self) {
+        self.notify.notify_waiters();
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.state.lock().unwrap().paused = paused;
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    // Ya no van a llegar más enlaces (modos sin interfaz)
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().finished
+    }
+
+    // Los elementos pendientes quedan guardados para la próxima sesión
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_waiters();
+    }
+
+    pub fn finish(&self) {
+        self.state.lock().unwrap().finished = true;
+        self.notify.notify_waiters();
+    }
+
+    fn persist(&self, state: &QueueState) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Ok(json) = serde_json::to_string_pretty(&state.items) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+}
+
+pub fn load_saved(path: &Path) -> Vec<QueueItem> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_progress_counts_completed_and_failed() {
+        let queue = DownloadQueue::new(None, Vec::new());
+        let single = queue.push("a".to_string(), None);
+        let ids = queue.push_batch(vec!["b".to_string(), "c".to_string(), "d".to_string()], None);
+
+        assert!(queue.complete(single, true).is_none());
+
+        let progress = queue.complete(ids[0], false).unwrap();
+        assert_eq!((progress.done, progress.total, progress.failed), (1, 3, 1));
+
+        queue.complete(ids[1], true);
+        let progress = queue.complete(ids[2], true).unwrap();
+        assert_eq!((progress.done, progress.failed), (3, 1));
+        assert!(progress.is_finished());
+    }
+
+    #[test]
+    fn item_state_transitions() {
+        let downloading = ItemState::Downloading { pct: Some(40.0) };
+
+        assert!(ItemState::Queued.can_become(&ItemState::FetchingMetadata));
+        assert!(!ItemState::Queued.can_become(&ItemState::Moving));
+        assert!(ItemState::FetchingMetadata.can_become(&downloading));
+        assert!(downloading.can_become(&ItemState::Downloading { pct: Some(80.0) }));
+        assert!(downloading.can_become(&ItemState::Moving));
+        // Siguiente tema de una playlist
+        assert!(ItemState::Moving.can_become(&ItemState::Downloading { pct: None }));
+        assert!(ItemState::Queued.can_become(&ItemState::Cancelled));
+        assert!(!ItemState::Done.can_become(&ItemState::Error));
+        assert!(!ItemState::Cancelled.can_become(&downloading));
+    }
+}
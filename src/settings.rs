@@ -0,0 +1,170 @@
+use crate::config::Config;
+
+// Formatos que se pueden elegir desde la pantalla de ajustes
+const AUDIO_FORMATS: [&str; 8] = ["mp3", "m4a", "aac", "alac", "flac", "opus", "vorbis", "wav"];
+const MAX_CONCURRENT_DOWNLOADS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    AudioFormat,
+    PreferFreeFormats,
+    AudioQuality,
+    DestDir,
+    KeepOriginalName,
+    Concurrency,
+    EmbedThumbnail,
+    Sponsorblock,
+    NormalizeAudio,
+}
+
+const FIELDS: [Field; 9] = [
+    Field::AudioFormat,
+    Field::PreferFreeFormats,
+    Field::AudioQuality,
+    Field::DestDir,
+    Field::KeepOriginalName,
+    Field::Concurrency,
+    Field::EmbedThumbnail,
+    Field::Sponsorblock,
+    Field::NormalizeAudio,
+];
+
+// Copia editable de la configuración; se aplica al confirmar
+pub struct SettingsForm {
+    pub draft: Config,
+    selected: usize,
+}
+
+fn cycle<T: PartialEq + Copy>(options: &[T], current: T, forward: bool) -> T {
+    let index = options.iter().position(|option| *option == current).unwrap_or(0);
+    let next = if forward {
+        (index + 1) % options.len()
+    } else {
+        (index + options.len() - 1) % options.len()
+    };
+    options[next]
+}
+
+pub fn cycle_audio_format(config: &mut Config, forward: bool) {
+    let current = AUDIO_FORMATS
+        .iter()
+        .copied()
+        .find(|format| *format == config.audio_format)
+        .unwrap_or(AUDIO_FORMATS[0]);
+    config.audio_format = cycle(&AUDIO_FORMATS, current, forward).to_string();
+}
+
+// Calidad VBR de yt-dlp: 0 (mejor) a 9; un bitrate fijo vuelve a 0
+fn audio_quality_level(config: &Config) -> u8 {
+    config.audio_quality.parse::<u8>().ok().filter(|q| *q <= 9).unwrap_or(0)
+}
+
+// Sin dar la vuelta: "mejor" se queda en 0 y "peor" en 9
+pub fn step_audio_quality(config: &mut Config, better: bool) {
+    let current = audio_quality_level(config);
+    let next = if better { current.saturating_sub(1) } else { (current + 1).min(9) };
+    config.audio_quality = next.to_string();
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "sí"
+    } else {
+        "no"
+    }
+}
+
+impl SettingsForm {
+    pub fn new(config: Config) -> Self {
+        SettingsForm { draft: config, selected: 0 }
+    }
+
+    fn field(&self) -> Field {
+        FIELDS[self.selected]
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % FIELDS.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = (self.selected + FIELDS.len() - 1) % FIELDS.len();
+    }
+
+    // El destino se escribe; el resto de los campos se cambia con ←/→ o Espacio
+    pub fn edits_text(&self) -> bool {
+        self.field() == Field::DestDir
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        if self.edits_text() {
+            self.draft.dest_dir.get_or_insert_with(String::new).push(c);
+        }
+    }
+
+    pub fn pop_char(&mut self) {
+        if !self.edits_text() {
+            return;
+        }
+        if let Some(dest) = self.draft.dest_dir.as_mut() {
+            dest.pop();
+        }
+    }
+
+    pub fn change(&mut self, forward: bool) {
+        let field = self.field();
+        let draft = &mut self.draft;
+        match field {
+            Field::AudioFormat => cycle_audio_format(draft, forward),
+            Field::AudioQuality => {
+                let current = audio_quality_level(draft);
+                let qualities: Vec<u8> = (0..=9).collect();
+                draft.audio_quality = cycle(&qualities, current, forward).to_string();
+            }
+            Field::Concurrency => {
+                let options: Vec<usize> = (1..=MAX_CONCURRENT_DOWNLOADS).collect();
+                draft.max_concurrent_downloads = cycle(&options, draft.max_concurrent_downloads, forward);
+            }
+            Field::PreferFreeFormats => draft.prefer_free_formats = !draft.prefer_free_formats,
+            Field::KeepOriginalName => draft.keep_original_name = !draft.keep_original_name,
+            Field::EmbedThumbnail => draft.embed_thumbnail = !draft.embed_thumbnail,
+            Field::Sponsorblock => draft.sponsorblock = !draft.sponsorblock,
+            Field::NormalizeAudio => draft.normalize_audio = !draft.normalize_audio,
+            Field::DestDir => {}
+        }
+    }
+
+    // (seleccionado, texto) por cada campo
+    pub fn lines(&self) -> Vec<(bool, String)> {
+        let draft = &self.draft;
+        FIELDS
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let text = match field {
+                    Field::AudioFormat if draft.effective_audio_format() != draft.audio_format => format!(
+                        "Formato:                < {} > (se usa {})",
+                        draft.audio_format,
+                        draft.effective_audio_format()
+                    ),
+                    Field::AudioFormat => format!("Formato:                < {} >", draft.audio_format),
+                    Field::PreferFreeFormats => {
+                        format!("Preferir formatos libres (opus/vorbis/flac): {}", on_off(draft.prefer_free_formats))
+                    }
+                    Field::AudioQuality => format!("Calidad (0 = mejor):    < {} >", draft.audio_quality),
+                    Field::DestDir => format!("Destino:                {}", draft.dest_dir.as_deref().unwrap_or("")),
+                    Field::KeepOriginalName => {
+                        format!("Nombre original de yt-dlp: {}", on_off(draft.keep_original_name))
+                    }
+                    Field::Concurrency => {
+                        format!("Descargas simultáneas:  < {} >", draft.max_concurrent_downloads)
+                    }
+                    Field::EmbedThumbnail => format!("Portada incrustada:     {}", on_off(draft.embed_thumbnail)),
+                    Field::Sponsorblock => format!("Quitar partes sin música (SponsorBlock): {}", on_off(draft.sponsorblock)),
+                    Field::NormalizeAudio => format!("Normalizar volumen:     {}", on_off(draft.normalize_audio)),
+                };
+                (index == self.selected, text)
+            })
+            .collect()
+    }
+}
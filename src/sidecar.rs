@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config::SidecarFormat;
+
+// Lo que se guarda junto al audio para el media server
+#[derive(Serialize, Debug)]
+pub struct SidecarData {
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    // AAAA-MM-DD
+    pub upload_date: Option<String>,
+    pub duration: Option<u64>,
+    pub url: String,
+    pub description: Option<String>,
+}
+
+impl SidecarData {
+    // Sin título o artista el sidecar no le sirve al media server
+    pub fn is_complete(&self) -> bool {
+        !self.title.trim().is_empty() && !self.artist.trim().is_empty()
+    }
+}
+
+// yt-dlp entrega la fecha como AAAAMMDD
+pub fn format_upload_date(raw: &str) -> Option<String> {
+    if raw.len() != 8 || !raw.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}-{}-{}", &raw[0..4], &raw[4..6], &raw[6..8]))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Formato .nfo de Kodi para videos musicales
+fn to_nfo(data: &SidecarData) -> String {
+    let mut nfo = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<musicvideo>\n");
+
+    let mut tag = |name: &str, value: &str| {
+        nfo.push_str(&format!("  <{0}>{1}</{0}>\n", name, escape_xml(value)));
+    };
+
+    tag("title", &data.title);
+    tag("artist", &data.artist);
+    if let Some(album) = &data.album {
+        tag("album", album);
+    }
+    if let Some(track_number) = data.track_number {
+        tag("track", &track_number.to_string());
+    }
+    if let Some(date) = &data.upload_date {
+        tag("premiered", date);
+        tag("year", &date[0..4]);
+    }
+    if let Some(duration) = data.duration {
+        // Kodi espera minutos
+        tag("runtime", &(duration / 60).to_string());
+    }
+    if let Some(description) = &data.description {
+        tag("plot", description);
+    }
+    tag("trailer", &data.url);
+
+    nfo.push_str("</musicvideo>\n");
+    nfo
+}
+
+// Escribe `<tema>.info.json` o `<tema>.nfo` al lado del audio
+pub fn write(audio_path: &Path, format: SidecarFormat, data: &SidecarData) -> Result<PathBuf, String> {
+    let (path, contents) = match format {
+        SidecarFormat::Json => (
+            audio_path.with_extension("info.json"),
+            serde_json::to_string_pretty(data).map_err(|e| e.to_string())?,
+        ),
+        SidecarFormat::Nfo => (audio_path.with_extension("nfo"), to_nfo(data)),
+    };
+
+    std::fs::write(&path, contents).map_err(|e| format!("No se pudo escribir {:?}: {}", path, e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nfo_escapes_text_and_formats_date() {
+        let data = SidecarData {
+            title: "Rock & Roll <Live>".to_string(),
+            artist: "Banda".to_string(),
+            album: None,
+            track_number: None,
+            upload_date: format_upload_date("20240315"),
+            duration: Some(245),
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            description: None,
+        };
+
+        let nfo = to_nfo(&data);
+        assert!(nfo.contains("<title>Rock &amp; Roll &lt;Live&gt;</title>"));
+        assert!(nfo.contains("<premiered>2024-03-15</premiered>"));
+        assert!(nfo.contains("<year>2024</year>"));
+        assert_eq!(format_upload_date("2024-03"), None);
+    }
+}
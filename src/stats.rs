@@ -0,0 +1,117 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+pub const STATS_FILE: &str = "stats.json";
+
+// Días que muestra el resumen de la interfaz
+pub const SUMMARY_DAYS: usize = 7;
+
+static STATS_LOCK: Mutex<()> = Mutex::new(());
+
+// Lo descargado desde que se abrió la app (o desde el último Ctrl+N); no se guarda
+static SESSION_DOWNLOADS: AtomicU32 = AtomicU32::new(0);
+static SESSION_BYTES: AtomicU64 = AtomicU64::new(0);
+
+// (descargas, bytes) de la sesión
+pub fn session() -> (u32, u64) {
+    (SESSION_DOWNLOADS.load(Ordering::SeqCst), SESSION_BYTES.load(Ordering::SeqCst))
+}
+
+pub fn reset_session() {
+    SESSION_DOWNLOADS.store(0, Ordering::SeqCst);
+    SESSION_BYTES.store(0, Ordering::SeqCst);
+}
+
+// Totales de un día (UTC)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DayStats {
+    // "AAAA-MM-DD"
+    pub date: String,
+    pub downloads: u32,
+    pub bytes: u64,
+}
+
+// Fecha UTC de unos segundos desde UNIX_EPOCH, sin depender de una biblioteca de fechas
+pub fn date_of(secs: u64) -> String {
+    // Algoritmo "days from civil" de Howard Hinnant, al revés
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn load(path: &Path) -> Result<Vec<DayStats>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("No se pudieron leer las estadísticas {:?}: {}", path, e))?;
+
+    serde_json::from_str(&raw).map_err(|e| format!("Estadísticas inválidas en {:?}: {}", path, e))
+}
+
+fn add(days: &mut Vec<DayStats>, date: String, bytes: u64) {
+    match days.iter_mut().find(|day| day.date == date) {
+        Some(day) => {
+            day.downloads += 1;
+            day.bytes += bytes;
+        }
+        None => {
+            days.push(DayStats { date, downloads: 1, bytes });
+            days.sort_by(|a, b| a.date.cmp(&b.date));
+        }
+    }
+}
+
+// Suma una descarga terminada al día de `at` y a la sesión
+pub fn record(path: &Path, at: u64, bytes: u64) -> Result<(), String> {
+    SESSION_DOWNLOADS.fetch_add(1, Ordering::SeqCst);
+    SESSION_BYTES.fetch_add(bytes, Ordering::SeqCst);
+
+    let _guard = STATS_LOCK.lock().unwrap();
+
+    let mut days = load(path)?;
+    add(&mut days, date_of(at), bytes);
+
+    let json = serde_json::to_string_pretty(&days).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("No se pudieron guardar las estadísticas {:?}: {}", path, e))
+}
+
+// Los últimos `count` días con descargas, del más reciente al más viejo
+pub fn last_days(days: &[DayStats], count: usize) -> Vec<DayStats> {
+    days.iter().rev().take(count).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates_downloads_per_day() {
+        assert_eq!(date_of(0), "1970-01-01");
+        assert_eq!(date_of(951_782_400), "2000-02-29");
+        assert_eq!(date_of(1_767_225_599), "2025-12-31");
+
+        let mut days = Vec::new();
+        add(&mut days, date_of(1_767_225_599), 100);
+        add(&mut days, date_of(1_767_225_600), 10);
+        add(&mut days, date_of(1_767_225_500), 50);
+
+        assert_eq!(
+            last_days(&days, 1),
+            vec![DayStats { date: "2026-01-01".to_string(), downloads: 1, bytes: 10 }]
+        );
+        assert_eq!(days[0], DayStats { date: "2025-12-31".to_string(), downloads: 2, bytes: 150 });
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SendError};
+
+use tokio::sync::oneshot;
+
+use crate::queue::ItemState;
+
+#[derive(Debug)]
+pub enum StatusEvent {
+    Message(String),
+    // Se muestra igual que un mensaje, pero sin interfaz va a stderr (y se mantiene con --quiet)
+    Error(String),
+    // Fase en curso sin progreso medible (metadata, actualización de binarios...)
+    Busy(String),
+    Idle,
+    Batch(BatchProgress),
+    // Ruta final de una descarga terminada
+    Downloaded(PathBuf),
+    // Cambio de estado de un elemento de la cola (por id)
+    Item(u64, ItemState),
+    // Nombre con demasiadas copias en el destino; la descarga espera la respuesta
+    Collision(CollisionPrompt),
+    // Canal fuera de channel_allowlist; la descarga espera la confirmación
+    Channel(ChannelPrompt),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionChoice {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+#[derive(Debug)]
+pub struct CollisionPrompt {
+    // El archivo sin sufijo que ya está en el destino
+    pub path: PathBuf,
+    // Sufijo que tomaría el nuevo ("_7")
+    pub suffix: u32,
+    pub reply: oneshot::Sender<CollisionChoice>,
+}
+
+#[derive(Debug)]
+pub struct ChannelPrompt {
+    pub author: String,
+    pub url: String,
+    // true: descargar igual
+    pub reply: oneshot::Sender<bool>,
+}
+
+// Avance de un lote: una playlist o varios enlaces encolados juntos
+#[derive(Debug, Clone)]
+pub struct BatchProgress {
+    pub key: String,
+    pub total: usize,
+    // Terminados, con o sin error
+    pub done: usize,
+    pub failed: usize,
+    // Los que no se van a descargar (fail-fast)
+    pub skipped: usize,
+}
+
+impl BatchProgress {
+    pub fn is_finished(&self) -> bool {
+        self.done + self.skipped >= self.total
+    }
+
+    pub fn label(&self) -> String {
+        format!("Batch: {}/{} ({} failed)", self.done, self.total, self.failed)
+    }
+}
+
+#[derive(Clone)]
+pub struct StatusSender {
+    tx: mpsc::Sender<StatusEvent>,
+    // Elemento de la cola al que pertenecen los cambios de estado
+    item: Option<u64>,
+}
+
+pub fn channel() -> (StatusSender, Receiver<StatusEvent>) {
+    let (tx, rx) = mpsc::channel();
+    (StatusSender { tx, item: None }, rx)
+}
+
+impl StatusSender {
+    pub fn send(&self, message: String) -> Result<(), SendError<StatusEvent>> {
+        self.tx.send(StatusEvent::Message(message))
+    }
+
+    pub fn error(&self, message: String) -> Result<(), SendError<StatusEvent>> {
+        self.tx.send(StatusEvent::Error(message))
+    }
+
+    pub fn batch(&self, progress: BatchProgress) -> Result<(), SendError<StatusEvent>> {
+        self.tx.send(StatusEvent::Batch(progress))
+    }
+
+    pub fn downloaded(&self, path: PathBuf) -> Result<(), SendError<StatusEvent>> {
+        self.tx.send(StatusEvent::Downloaded(path))
+    }
+
+    // Copia que informa los cambios de estado como del elemento `id`
+    pub fn for_item(&self, id: u64) -> StatusSender {
+        StatusSender { tx: self.tx.clone(), item: Some(id) }
+    }
+
+    // Sin elemento asociado (modo headless sin cola) no hace nada
+    pub fn stage(&self, state: ItemState) {
+        if let Some(id) = self.item {
+            let _ = self.tx.send(StatusEvent::Item(id, state));
+        }
+    }
+
+    // Sin nadie que conteste (modo sin interfaz, respuesta descartada) se renombra, como siempre
+    pub async fn ask_collision(&self, path: PathBuf, suffix: u32) -> CollisionChoice {
+        let (reply, answer) = oneshot::channel();
+        if self.tx.send(StatusEvent::Collision(CollisionPrompt { path, suffix, reply })).is_err() {
+            return CollisionChoice::Rename;
+        }
+        answer.await.unwrap_or(CollisionChoice::Rename)
+    }
+
+    // Sin nadie que conteste se omite: en modo sin interfaz hace falta --force
+    pub async fn ask_channel(&self, author: String, url: String) -> bool {
+        let (reply, answer) = oneshot::channel();
+        if self.tx.send(StatusEvent::Channel(ChannelPrompt { author, url, reply })).is_err() {
+            return false;
+        }
+        answer.await.unwrap_or(false)
+    }
+
+    // Marca una fase como "en curso" hasta que se suelta el guard devuelto
+    pub fn busy(&self, phase: &str) -> BusyGuard {
+        let _ = self.tx.send(StatusEvent::Busy(phase.to_string()));
+        BusyGuard(self.tx.clone())
+    }
+}
+
+pub struct BusyGuard(mpsc::Sender<StatusEvent>);
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        let _ = self.0.send(StatusEvent::Idle);
+    }
+}
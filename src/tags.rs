@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use id3::{Tag, TagLike, Version};
+
+// Etiquetas que se escriben en el archivo de audio
+#[derive(Debug, Clone)]
+pub struct TrackTags {
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+    pub year: Option<i32>,
+}
+
+// Por ahora solo ID3 (mp3, wav, aiff)
+pub fn supports(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref(),
+        Some("mp3") | Some("wav") | Some("aiff") | Some("aif")
+    )
+}
+
+fn read_tag(path: &Path) -> Result<Tag, String> {
+    match Tag::read_from_path(path) {
+        Ok(tag) => Ok(tag),
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => Ok(Tag::new()),
+        Err(e) => Err(format!("No se pudieron leer las etiquetas de {:?}: {}", path, e)),
+    }
+}
+
+// Cambios que haría `write`, como líneas "campo: antes -> después"
+pub fn diff(path: &Path, tags: &TrackTags) -> Result<Vec<String>, String> {
+    let current = read_tag(path)?;
+    let mut changes = Vec::new();
+
+    let mut compare = |field: &str, old: Option<String>, new: Option<String>| {
+        if new.is_some() && old != new {
+            changes.push(format!(
+                "{}: {} -> {}",
+                field,
+                old.unwrap_or_else(|| "(vacío)".to_string()),
+                new.unwrap_or_default()
+            ));
+        }
+    };
+
+    compare("título", current.title().map(str::to_string), Some(tags.title.clone()));
+    compare("artista", current.artist().map(str::to_string), Some(tags.artist.clone()));
+    compare("álbum", current.album().map(str::to_string), tags.album.clone());
+    compare("pista", current.track().map(|n| n.to_string()), tags.track.map(|n| n.to_string()));
+    compare("año", current.year().map(|y| y.to_string()), tags.year.map(|y| y.to_string()));
+
+    Ok(changes)
+}
+
+pub fn write(path: &Path, tags: &TrackTags) -> Result<(), String> {
+    let mut tag = read_tag(path)?;
+
+    tag.set_title(tags.title.as_str());
+    tag.set_artist(tags.artist.as_str());
+    if let Some(album) = &tags.album {
+        tag.set_album(album.as_str());
+    }
+    if let Some(track) = tags.track {
+        tag.set_track(track);
+    }
+    if let Some(year) = tags.year {
+        tag.set_year(year);
+    }
+
+    tag.write_to_path(path, Version::Id3v24)
+        .map_err(|e| format!("No se pudieron escribir las etiquetas en {:?}: {}", path, e))
+}
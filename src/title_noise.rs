@@ -0,0 +1,93 @@
+use regex::{Captures, Regex};
+
+// Se comparan contra el texto completo dentro de () o [], sin distinguir mayúsculas
+pub const DEFAULT_NOISE_PATTERNS: [&str; 7] = [
+    r"official\s+(music\s+)?(video|audio|visualizer|lyric\s+video)",
+    r"(official\s+)?lyrics?(\s+video)?",
+    r"(official\s+)?visualizer",
+    r"(hd|hq|4k|1080p|720p)(\s+(video|audio|remaster(ed)?))?",
+    r"(video|audio)(\s+oficial)?",
+    r"(video|audio)\s*(oficial|clip)|videoclip(\s+oficial)?",
+    r"(con\s+)?letra",
+];
+
+pub fn compile(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(&format!(r"^(?i:{})$", pattern))
+                .map_err(|e| format!("Patrón de título inválido {:?}: {}", pattern, e))
+        })
+        .collect()
+}
+
+// Quita los grupos entre paréntesis/corchetes que son solo ruido; "(Remix)" o "(feat. X)" se mantienen
+pub fn strip_noise(title: &str, patterns: &[Regex]) -> String {
+    let group = Regex::new(r"\s*[\(\[]([^\(\)\[\]]*)[\)\]]").unwrap();
+
+    let cleaned = group.replace_all(title, |caps: &Captures| {
+        if patterns.iter().any(|pattern| pattern.is_match(caps[1].trim())) {
+            String::new()
+        } else {
+            caps[0].to_string()
+        }
+    });
+
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let cleaned = cleaned.trim_end_matches(|c: char| c == '-' || c == '|' || c.is_whitespace());
+
+    // Si el título era solo ruido se deja como estaba
+    if cleaned.is_empty() {
+        title.trim().to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Vec<Regex> {
+        let patterns: Vec<String> = DEFAULT_NOISE_PATTERNS.iter().map(|p| p.to_string()).collect();
+        compile(&patterns).unwrap()
+    }
+
+    #[test]
+    fn strips_common_noise() {
+        let patterns = defaults();
+        let cases = [
+            ("Artist - Song (Official Music Video)", "Artist - Song"),
+            ("Artist - Song [Official Audio]", "Artist - Song"),
+            ("Song (Lyric Video)", "Song"),
+            ("Song (Lyrics)", "Song"),
+            ("Song [HD]", "Song"),
+            ("Song (Official Video) [4K]", "Song"),
+            ("Canción (Video Oficial)", "Canción"),
+            ("Canción (Letra)", "Canción"),
+            ("Song (Official Visualizer)", "Song"),
+        ];
+
+        for (title, expected) in cases {
+            assert_eq!(strip_noise(title, &patterns), expected, "{}", title);
+        }
+    }
+
+    #[test]
+    fn keeps_meaningful_parentheses() {
+        let patterns = defaults();
+        let cases = [
+            "Song (Remix)",
+            "Song (feat. Someone)",
+            "Song (Live at Wembley)",
+            "Song (Acoustic Version)",
+            "Song (Video Game Theme)",
+        ];
+
+        for title in cases {
+            assert_eq!(strip_noise(title, &patterns), title);
+        }
+
+        assert_eq!(strip_noise("(Official Video)", &patterns), "(Official Video)");
+    }
+}
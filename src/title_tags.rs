@@ -0,0 +1,81 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Una regla de reescritura del título: regex y reemplazo (con $1, ${1}, como en `Regex::replace_all`)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TagRule {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+// Reglas por defecto: solo unifican la forma, no mueven ni quitan nada del título
+pub const DEFAULT_TAG_RULES: [(&str, &str); 4] = [
+    // "[X Remix]" -> "(X Remix)"
+    (r"\[([^\[\]]*\b(?i:remix|edit|mix|rework|bootleg)\b[^\[\]]*)\]", "($1)"),
+    // "(X remix)" / "(X REMIX)" -> "(X Remix)"
+    (r"(?i)\(([^()]*)\bremix\)", "(${1}Remix)"),
+    (r"(?i)\(([^()]*)\bedit\)", "(${1}Edit)"),
+    // "ft.", "Ft", "featuring", "Feat." -> "feat."
+    (r"(?i)\b(?:feat\.?|ft\.?|featuring)\s+", "feat. "),
+];
+
+pub fn default_rules() -> Vec<TagRule> {
+    DEFAULT_TAG_RULES
+        .iter()
+        .map(|(pattern, replacement)| TagRule { pattern: pattern.to_string(), replacement: replacement.to_string() })
+        .collect()
+}
+
+pub fn compile(rules: &[TagRule]) -> Result<Vec<(Regex, String)>, String> {
+    rules
+        .iter()
+        .map(|rule| {
+            Regex::new(&rule.pattern)
+                .map(|regex| (regex, rule.replacement.clone()))
+                .map_err(|e| format!("Regla de título inválida {:?}: {}", rule.pattern, e))
+        })
+        .collect()
+}
+
+// Aplica las reglas en orden, antes de limpiar el nombre de archivo
+pub fn normalize(title: &str, rules: &[(Regex, String)]) -> String {
+    let normalized = rules
+        .iter()
+        .fold(title.to_string(), |title, (regex, replacement)| regex.replace_all(&title, replacement.as_str()).into_owned());
+
+    normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unifies_featuring_and_remix_tags() {
+        let rules = compile(&default_rules()).unwrap();
+        let cases = [
+            ("Song (feat. X) [Y Remix]", "Song (feat. X) (Y Remix)"),
+            ("Song (Feat. X)", "Song (feat. X)"),
+            ("Song (ft. X)", "Song (feat. X)"),
+            ("Song ft X", "Song feat. X"),
+            ("Song (Featuring X & Z)", "Song (feat. X & Z)"),
+            ("Song [Radio Edit]", "Song (Radio Edit)"),
+            ("Song (y remix)", "Song (y Remix)"),
+            ("Song (Club  EDIT)", "Song (Club Edit)"),
+        ];
+
+        for (title, expected) in cases {
+            assert_eq!(normalize(title, &rules), expected, "{}", title);
+        }
+    }
+
+    #[test]
+    fn leaves_unrelated_words_alone() {
+        let rules = compile(&default_rules()).unwrap();
+        let cases = ["Daft Punk - Around the World", "Left Behind", "Feather", "Song [Live]", "Mixtape Intro"];
+
+        for title in cases {
+            assert_eq!(normalize(title, &rules), title);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YoutubeUrl {
+    pub video_id: Option<String>,
+    pub playlist_id: Option<String>,
+}
+
+impl YoutubeUrl {
+    // Un enlace con `v=` y `list=` puede significar un tema o la playlist entera
+    pub fn is_ambiguous(&self) -> bool {
+        self.video_id.is_some() && self.playlist_id.is_some()
+    }
+
+    pub fn is_playlist_only(&self) -> bool {
+        self.video_id.is_none() && self.playlist_id.is_some()
+    }
+
+    // Los álbumes de YouTube Music usan playlists con prefijo OLAK5uy_
+    pub fn is_album(&self) -> bool {
+        self.playlist_id
+            .as_deref()
+            .map(|id| id.starts_with("OLAK5uy_"))
+            .unwrap_or(false)
+    }
+
+    pub fn single_url(&self) -> Option<String> {
+        self.video_id
+            .as_ref()
+            .map(|id| format!("https://www.youtube.com/watch?v={}", id))
+    }
+
+    pub fn playlist_url(&self) -> Option<String> {
+        self.playlist_id
+            .as_ref()
+            .map(|id| format!("https://www.youtube.com/playlist?list={}", id))
+    }
+}
+
+// Enlace aceptado: de YouTube, o de otro sitio que se pasa tal cual a yt-dlp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaUrl {
+    Youtube(YoutubeUrl),
+    Other(String),
+}
+
+const YOUTUBE_HOSTS: [&str; 3] = ["youtube.com", "music.youtube.com", "youtu.be"];
+
+fn host_of(input: &str) -> &str {
+    let trimmed = input.trim();
+    let without_scheme = trimmed
+        .strip_prefix("https://")
+        .or_else(|| trimmed.strip_prefix("http://"))
+        .unwrap_or(trimmed);
+    without_scheme
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or("")
+        .trim_start_matches("www.")
+        .trim_start_matches("m.")
+}
+
+// Cualquier enlace http(s) bien formado; si yt-dlp no soporta el sitio falla al descargar
+pub fn parse_other_site_url(input: &str) -> Result<String, String> {
+    let trimmed = input.trim();
+    if !(trimmed.starts_with("https://") || trimmed.starts_with("http://")) {
+        return Err(format!("El enlace debe empezar con http:// o https://: {}", trimmed));
+    }
+
+    let host_pattern = Regex::new(r"^[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)+(:\d+)?$").unwrap();
+    if !host_pattern.is_match(host_of(trimmed)) || trimmed.chars().any(char::is_whitespace) {
+        return Err(format!("Enlace inválido: {}", trimmed));
+    }
+
+    Ok(trimmed.to_string())
+}
+
+// Un enlace de YouTube mal formado sigue siendo un error aunque se permitan otros sitios
+pub fn parse_media_url(input: &str, allow_other_sites: bool) -> Result<MediaUrl, String> {
+    match parse_youtube_url(input) {
+        Ok(parsed) => Ok(MediaUrl::Youtube(parsed)),
+        Err(e) if !allow_other_sites || YOUTUBE_HOSTS.contains(&host_of(input)) => Err(e),
+        Err(_) => parse_other_site_url(input).map(MediaUrl::Other),
+    }
+}
+
+// Clave para limitar descargas simultáneas por sitio; todos los dominios de YouTube cuentan como uno
+pub fn host_key(input: &str) -> String {
+    let host = host_of(input).to_lowercase();
+    let host = host.split(':').next().unwrap_or("").to_string();
+    if YOUTUBE_HOSTS.contains(&host.as_str()) {
+        "youtube.com".to_string()
+    } else {
+        host
+    }
+}
+
+// Mismo video o playlist aunque cambie la forma del enlace (youtu.be, music., &t=)
+pub fn queue_key(url: &str) -> String {
+    match parse_youtube_url(url) {
+        Ok(YoutubeUrl { video_id: Some(id), .. }) => format!("video:{}", id),
+        Ok(YoutubeUrl { playlist_id: Some(id), .. }) => format!("playlist:{}", id),
+        _ => url.trim().trim_end_matches('/').to_string(),
+    }
+}
+
+pub fn parse_youtube_url(input: &str) -> Result<YoutubeUrl, String> {
+    let trimmed = input.trim();
+    let without_scheme = trimmed
+        .strip_prefix("https://")
+        .or_else(|| trimmed.strip_prefix("http://"))
+        .unwrap_or(trimmed);
+    let without_fragment = without_scheme.split('#').next().unwrap_or("");
+
+    let (location, query) = without_fragment
+        .split_once('?')
+        .unwrap_or((without_fragment, ""));
+    let (host, path) = location.split_once('/').unwrap_or((location, ""));
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+    };
+
+    let path_segment = |prefix: &str| {
+        path.strip_prefix(prefix)
+            .and_then(|rest| rest.split('/').next())
+            .map(|id| id.to_string())
+    };
+
+    let video_id = match host {
+        "youtube.com" | "music.youtube.com" => {
+            if path == "watch" {
+                param("v")
+            } else {
+                path_segment("shorts/")
+                    .or_else(|| path_segment("embed/"))
+                    .or_else(|| path_segment("live/"))
+            }
+        }
+        "youtu.be" => path_segment(""),
+        _ => return Err(format!("No es un enlace de YouTube: {}", trimmed)),
+    };
+    let playlist_id = param("list");
+
+    let video_id_pattern = Regex::new(r"^[A-Za-z0-9_-]{11}$").unwrap();
+    let playlist_id_pattern = Regex::new(r"^[A-Za-z0-9_-]+$").unwrap();
+
+    if let Some(id) = &video_id {
+        if !video_id_pattern.is_match(id) {
+            return Err(format!("ID de video inválido: {}", id));
+        }
+    }
+    if let Some(id) = &playlist_id {
+        if !playlist_id_pattern.is_match(id) {
+            return Err(format!("ID de playlist inválido: {}", id));
+        }
+    }
+
+    if video_id.is_none() && playlist_id.is_none() {
+        return Err(format!("El enlace no contiene un video ni una playlist: {}", trimmed));
+    }
+
+    Ok(YoutubeUrl { video_id, playlist_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_watch_and_short_links() {
+        let watch = parse_youtube_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42").unwrap();
+        assert_eq!(watch.video_id.as_deref(), Some("dQw4w9WgXcQ"));
+        assert!(!watch.is_ambiguous());
+
+        let short = parse_youtube_url("youtu.be/dQw4w9WgXcQ").unwrap();
+        assert_eq!(short.single_url(), watch.single_url());
+    }
+
+    #[test]
+    fn detects_video_inside_playlist() {
+        let url = parse_youtube_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1234abcd").unwrap();
+        assert!(url.is_ambiguous());
+        assert_eq!(
+            url.playlist_url().as_deref(),
+            Some("https://www.youtube.com/playlist?list=PL1234abcd")
+        );
+    }
+
+    #[test]
+    fn detects_music_album_links() {
+        let album = parse_youtube_url("https://music.youtube.com/playlist?list=OLAK5uy_k2ts2tGg3jxyFlCgx2hX4_MfkUHdWyJZY").unwrap();
+        assert!(album.is_playlist_only());
+        assert!(album.is_album());
+
+        let playlist = parse_youtube_url("https://www.youtube.com/playlist?list=PL1234abcd").unwrap();
+        assert!(!playlist.is_album());
+    }
+
+    #[test]
+    fn other_sites_only_when_allowed() {
+        let soundcloud = "https://soundcloud.com/artista/tema";
+        assert!(parse_media_url(soundcloud, false).is_err());
+        assert_eq!(
+            parse_media_url(soundcloud, true),
+            Ok(MediaUrl::Other(soundcloud.to_string()))
+        );
+
+        assert!(parse_media_url("https://www.youtube.com/watch?v=short", true).is_err());
+        assert!(parse_media_url("soundcloud.com/artista/tema", true).is_err());
+        assert!(matches!(
+            parse_media_url("https://youtu.be/dQw4w9WgXcQ", true),
+            Ok(MediaUrl::Youtube(_))
+        ));
+    }
+
+    #[test]
+    fn youtube_hosts_share_a_key() {
+        assert_eq!(host_key("https://music.youtube.com/watch?v=dQw4w9WgXcQ"), "youtube.com");
+        assert_eq!(host_key("youtu.be/dQw4w9WgXcQ"), "youtube.com");
+        assert_eq!(host_key("https://www.YouTube.com/watch?v=dQw4w9WgXcQ"), "youtube.com");
+        assert_eq!(host_key("https://soundcloud.com:443/artista/tema"), "soundcloud.com");
+    }
+
+    #[test]
+    fn same_video_shares_a_queue_key() {
+        assert_eq!(
+            queue_key("https://music.youtube.com/watch?v=dQw4w9WgXcQ&t=42"),
+            queue_key("youtu.be/dQw4w9WgXcQ")
+        );
+        assert_ne!(
+            queue_key("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            queue_key("https://www.youtube.com/playlist?list=PL1234abcd")
+        );
+        assert_eq!(queue_key("https://soundcloud.com/artista/tema/"), "https://soundcloud.com/artista/tema");
+    }
+
+    #[test]
+    fn rejects_foreign_and_malformed_links() {
+        assert!(parse_youtube_url("https://example.com/watch?v=dQw4w9WgXcQ").is_err());
+        assert!(parse_youtube_url("https://www.youtube.com/watch?v=short").is_err());
+        assert!(parse_youtube_url("https://www.youtube.com/feed/library").is_err());
+    }
+}